@@ -43,6 +43,41 @@ public class TestFrameworkRuntime : MonoBehaviour
 
     private bool _movieTestStarted;
 
+    /// <summary>
+    /// Frames a benchmark records before stopping by itself, which are the frames its movie plays.
+    /// A double, as that is what lua numbers are when the runner sets it
+    /// </summary>
+    private static double _benchmarkFrames;
+
+    private static int _benchmarkFramesRecorded;
+
+    private static bool _benchmarkRecording;
+
+    /// <summary>
+    /// Starts counting frames, timing them is left to the runner as UniTAS controls the clocks of the game while
+    /// the movie plays
+    /// </summary>
+    public static void StartBenchmark()
+    {
+        _benchmarkFramesRecorded = 0;
+        _benchmarkRecording = true;
+    }
+
+    public static int GetBenchmarkFrames()
+    {
+        return _benchmarkFramesRecorded;
+    }
+
+    private void Update()
+    {
+        if (!_benchmarkRecording) return;
+        _benchmarkFramesRecorded++;
+        if (_benchmarkFramesRecorded >= _benchmarkFrames)
+        {
+            _benchmarkRecording = false;
+        }
+    }
+
     private void Awake()
     {
         if (_instance != null)
@@ -172,12 +207,16 @@ public class TestFrameworkRuntime : MonoBehaviour
     private static IEnumerator RunTest(Test test, List<Result> results)
     {
         Debug.Log($"Running test {test.Name}");
+        var startFrame = Time.frameCount;
+        var startTime = Time.time;
+        var stopwatch = System.Diagnostics.Stopwatch.StartNew();
         var executeIter = test.Execute();
         while (executeIter.MoveNext())
         {
             if (executeIter.Current is Result result)
             {
-                results.Add(result);
+                results.Add(result.WithTiming(Time.frameCount - startFrame, Time.time - startTime,
+                    stopwatch.Elapsed.TotalSeconds));
                 break;
             }
 
@@ -290,12 +329,39 @@ public class TestFrameworkRuntime : MonoBehaviour
             Name = name;
             Message = message;
             Success = success;
+            Frames = 0;
+            GameTime = 0;
+            RealTime = 0;
         }
 
         public readonly string Name;
         public readonly string Message;
         public readonly bool Success;
 
+        /// <summary>
+        /// Frames the test took
+        /// </summary>
+        public int Frames;
+
+        /// <summary>
+        /// Seconds of Time.time the test took
+        /// </summary>
+        public float GameTime;
+
+        /// <summary>
+        /// Seconds the test took as measured by Stopwatch, which is real time unless UniTAS controls it
+        /// </summary>
+        public double RealTime;
+
+        public Result WithTiming(int frames, float gameTime, double realTime)
+        {
+            var result = this;
+            result.Frames = frames;
+            result.GameTime = gameTime;
+            result.RealTime = realTime;
+            return result;
+        }
+
         public override string ToString()
         {
             return Success ? string.Format("success: {0}", Name) : string.Format("failure: {0}: {1}", Name, Message);
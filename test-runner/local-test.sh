@@ -69,4 +69,4 @@ if [ -f "$unitas_rs_build" ]; then
 fi
 
 # shellcheck disable=SC2090,SC2086
-exec $test_dir/test-runner --github-token "$(gh auth token)" $game_args $bepinex_dir
+exec $test_dir/test-runner $game_args $bepinex_dir
@@ -0,0 +1,254 @@
+//! Statistics of benchmark runs, and comparing them against a baseline from an earlier build
+
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{cli::BenchArgs, symbols};
+
+/// Summary of a set of samples
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Stats {
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub min: f64,
+    pub max: f64,
+    pub stddev: f64,
+}
+
+impl Stats {
+    /// Unset if there are no samples
+    pub fn new(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let len = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / len;
+        let variance = sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / len;
+
+        Some(Self {
+            mean,
+            median: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+/// Nearest rank percentile of sorted samples
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// One play of a benchmark movie, timed by the runner
+#[derive(Clone, Copy, Debug)]
+pub struct BenchRun {
+    pub frames: u32,
+    pub secs: f64,
+}
+
+/// Result of running one benchmark of a game a number of times
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BenchResult {
+    pub runs: usize,
+    /// Frames per real second of each run
+    pub fps: Stats,
+    /// Mean milliseconds per frame of each run
+    pub frame_ms: Stats,
+}
+
+impl BenchResult {
+    pub fn new(runs: &[BenchRun]) -> Result<Self> {
+        let runs = runs
+            .iter()
+            .filter(|run| run.frames > 0 && run.secs > 0.0)
+            .collect::<Vec<_>>();
+        let fps = runs
+            .iter()
+            .map(|run| run.frames as f64 / run.secs)
+            .collect::<Vec<_>>();
+        let frame_ms = runs
+            .iter()
+            .map(|run| run.secs * 1000.0 / run.frames as f64)
+            .collect::<Vec<_>>();
+
+        // real time doesn't repeat itself exactly, a clock that does is controlled by UniTAS
+        if fps.len() > 1 && fps.iter().all(|&sample| sample == fps[0]) {
+            bail!("every run took exactly as long, the frames weren't timed in real time");
+        }
+
+        Ok(Self {
+            runs: runs.len(),
+            fps: Stats::new(&fps).context("no frames were recorded")?,
+            frame_ms: Stats::new(&frame_ms).context("no frames were recorded")?,
+        })
+    }
+}
+
+impl Display for BenchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1} fps (±{:.1}), frame time {:.3} ms mean, {:.3} ms p95, {:.3} ms max over {} runs",
+            self.fps.mean,
+            self.fps.stddev,
+            self.frame_ms.mean,
+            self.frame_ms.p95,
+            self.frame_ms.max,
+            self.runs
+        )
+    }
+}
+
+/// Benchmark results by `<game>/<benchmark>`
+#[derive(Serialize, Deserialize, Default)]
+pub struct Baseline {
+    pub benchmarks: BTreeMap<String, BenchResult>,
+}
+
+impl Baseline {
+    /// Empty if the file doesn't exist
+    pub async fn load(path: &Path) -> Result<Self> {
+        if !fs::try_exists(path).await.unwrap_or_default() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("failed to read benchmark baseline `{}`", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse benchmark baseline `{}`", path.display()))
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let contents =
+            serde_json::to_string_pretty(self).context("failed to serialize benchmark results")?;
+
+        fs::write(path, contents)
+            .await
+            .with_context(|| format!("failed to write benchmark results to `{}`", path.display()))
+    }
+}
+
+/// Changes against the baseline in percent, positive being slower
+#[derive(PartialEq, Debug)]
+pub struct Comparison {
+    pub fps_drop: f64,
+    pub frame_time_increase: f64,
+    pub regressed: bool,
+}
+
+/// Compares mean frames per second and the 95th percentile frame time of the runs against the thresholds
+pub fn compare(result: &BenchResult, baseline: &BenchResult, args: &BenchArgs) -> Comparison {
+    let fps_drop = (baseline.fps.mean - result.fps.mean) / baseline.fps.mean * 100.0;
+    let frame_time_increase =
+        (result.frame_ms.p95 - baseline.frame_ms.p95) / baseline.frame_ms.p95 * 100.0;
+
+    Comparison {
+        fps_drop,
+        frame_time_increase,
+        regressed: fps_drop > args.max_fps_drop as f64
+            || frame_time_increase > args.max_frame_time_increase as f64,
+    }
+}
+
+/// Prints the results next to the baseline, returning the names of benchmarks which regressed
+pub fn print_comparison(
+    results: &BTreeMap<String, BenchResult>,
+    baseline: &Baseline,
+    args: &BenchArgs,
+) -> Vec<String> {
+    let mut regressions = Vec::new();
+    for (name, result) in results {
+        println!("{name}: {result}");
+
+        let Some(base) = baseline.benchmarks.get(name) else {
+            println!("    no baseline to compare against\n");
+            continue;
+        };
+        let comparison = compare(result, base, args);
+        let change = format!(
+            "    {:+.1}% fps, {:+.1}% p95 frame time against the baseline",
+            -comparison.fps_drop, comparison.frame_time_increase
+        );
+        if comparison.regressed {
+            println!("{} {}\n", change.red(), "REGRESSION".red());
+            regressions.push(name.to_owned());
+        } else {
+            println!("{} {}\n", change, symbols::SUCCESS.green());
+        }
+    }
+
+    regressions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bench_args() -> BenchArgs {
+        BenchArgs {
+            runs: 3,
+            baseline: "bench-baseline.json".into(),
+            save_baseline: false,
+            max_fps_drop: 10,
+            max_frame_time_increase: 20,
+        }
+    }
+
+    #[test]
+    fn stats_of_samples() {
+        let stats = Stats::new(&[4.0, 1.0, 3.0, 2.0]).unwrap();
+
+        assert_eq!(stats.mean, 2.5);
+        assert_eq!(stats.median, 2.0);
+        assert_eq!(stats.p95, 4.0);
+        assert_eq!((stats.min, stats.max), (1.0, 4.0));
+        assert!((stats.stddev - 1.25f64.sqrt()).abs() < 1e-9);
+        assert_eq!(Stats::new(&[]), None);
+    }
+
+    fn runs(secs: &[f64]) -> Vec<BenchRun> {
+        secs.iter()
+            .map(|&secs| BenchRun { frames: 100, secs })
+            .collect()
+    }
+
+    #[test]
+    fn slower_build_is_a_regression() {
+        let baseline = BenchResult::new(&runs(&[0.9, 1.1])).unwrap();
+        assert!((baseline.fps.mean - 101.0101).abs() < 1e-3);
+        assert!((baseline.frame_ms.mean - 10.0).abs() < 1e-9);
+
+        let similar = BenchResult::new(&runs(&[0.95, 1.15])).unwrap();
+        let comparison = compare(&similar, &baseline, &bench_args());
+        assert!(!comparison.regressed);
+        assert!((comparison.frame_time_increase - 100.0 / 22.0).abs() < 1e-9);
+
+        let slower = BenchResult::new(&runs(&[1.2, 1.3])).unwrap();
+        let comparison = compare(&slower, &baseline, &bench_args());
+        assert!(comparison.regressed);
+
+        assert!(BenchResult::new(&[BenchRun {
+            frames: 0,
+            secs: 1.0
+        }])
+        .is_err());
+    }
+
+    #[test]
+    fn identical_runs_are_rejected() {
+        assert!(BenchResult::new(&runs(&[1.0 / 60.0; 3])).is_err());
+        assert!(BenchResult::new(&runs(&[1.0])).is_ok());
+    }
+}
@@ -0,0 +1,156 @@
+//! Config files of UniTAS and BepInEx, written into each game before it runs
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{cli::Args, games::GameSpec, ini};
+
+const BEPINEX_CFG: &str = "BepInEx.cfg";
+
+/// Writes `BepInEx/config/UniTAS.cfg`, returning the contents
+///
+/// Layered from the base config, settings for every game, settings of the game, then what the runner needs
+pub fn write_unitas_cfg(game_dir: &Path, game: &GameSpec, args: &Args) -> Result<String> {
+    let base = match &args.unitas_cfg {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("failed to read base UniTAS config `{}`", path.display()))?,
+        None => String::new(),
+    };
+
+    let contents = ini::Builder::new(&base)
+        .merge(&args.unitas_cfg_settings)
+        .merge(&game.unitas_cfg)
+        .set("Remote", "Enable", true)
+        .set("Remote", "Port", args.port)
+        .build();
+
+    write_config(game_dir, "UniTAS.cfg", &contents)?;
+    Ok(contents)
+}
+
+/// Writes `BepInEx/config/BepInEx.cfg` so logs are as detailed in every run, returning the contents
+///
+/// Based on the given base config or the one BepInEx wrote in an earlier run, settings from config and
+/// command line override the managed ones
+pub fn write_bepinex_cfg(game_dir: &Path, game: &GameSpec, args: &Args) -> Result<String> {
+    let existing = config_dir(game_dir).join(BEPINEX_CFG);
+    let base = match &args.bepinex_cfg {
+        Some(path) => read_base(path)?,
+        None if existing.is_file() => read_base(&existing)?,
+        None => String::new(),
+    };
+
+    let mut builder = ini::Builder::new(&base)
+        .set("Logging", "UnityLogListening", true)
+        .set("Logging.Disk", "Enabled", true)
+        .set("Logging.Disk", "WriteUnityLog", true)
+        .set("Logging.Disk", "AppendLog", false)
+        .set("Logging.Disk", "LogLevels", "All")
+        .set("Logging.Console", "Enabled", false)
+        .merge(&args.bepinex_cfg_settings)
+        .merge(&game.bepinex_cfg);
+    if let Some(levels) = &args.bepinex_log_levels {
+        builder = builder.set("Logging.Disk", "LogLevels", levels);
+    }
+    let contents = builder.build();
+
+    write_config(game_dir, BEPINEX_CFG, &contents)?;
+    Ok(contents)
+}
+
+fn read_base(path: &Path) -> Result<String> {
+    fs::read_to_string(path)
+        .with_context(|| format!("failed to read base config `{}`", path.display()))
+}
+
+fn config_dir(game_dir: &Path) -> PathBuf {
+    game_dir.join("BepInEx").join("config")
+}
+
+fn write_config(game_dir: &Path, file_name: &str, contents: &str) -> Result<()> {
+    let dir = config_dir(game_dir);
+    fs::create_dir_all(&dir).with_context(|| {
+        format!(
+            "failed to create directory for config files at path `{}`",
+            dir.display()
+        )
+    })?;
+
+    let path = dir.join(file_name);
+    fs::write(&path, contents).with_context(|| format!("failed to write `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::games;
+
+    #[test]
+    fn unitas_cfg_is_layered() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("UniTAS.cfg");
+        fs::write(
+            &base,
+            "## base config\n[Remote]\nPort = 1\n\n[Debug]\nVerbose = false\n",
+        )
+        .unwrap();
+
+        let mut args = Args::parse_from(["test-runner", "--port", "9000"]);
+        args.unitas_cfg = Some(base);
+        args.unitas_cfg_settings = toml::from_str("[Debug]\nVerbose = true\nExtra = 1").unwrap();
+        let mut game = games::registry(None, &Default::default())
+            .unwrap()
+            .remove(0);
+        game.unitas_cfg = toml::from_str("[Debug]\nExtra = 2\n[Remote]\nPort = 3").unwrap();
+
+        let contents = write_unitas_cfg(dir.path(), &game, &args).unwrap();
+
+        assert!(contents.starts_with("## base config\n"));
+        assert_eq!(ini::get_value(&contents, "Debug", "Verbose"), Some("true"));
+        assert_eq!(ini::get_value(&contents, "Debug", "Extra"), Some("2"));
+        // runner needs the remote on its port
+        assert_eq!(ini::get_value(&contents, "Remote", "Port"), Some("9000"));
+        assert_eq!(ini::get_value(&contents, "Remote", "Enable"), Some("true"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("BepInEx/config/UniTAS.cfg")).unwrap(),
+            contents
+        );
+    }
+
+    #[test]
+    fn bepinex_cfg_keeps_earlier_file_and_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_dir = config_dir(dir.path());
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join(BEPINEX_CFG),
+            "[Logging.Disk]\n\n## Only displays the specified log levels\nLogLevels = Fatal, Error\n",
+        )
+        .unwrap();
+
+        let mut args = Args::parse_from(["test-runner", "--bepinex-log-levels", "Info"]);
+        args.bepinex_cfg_settings =
+            toml::from_str("[\"Logging.Console\"]\nEnabled = true").unwrap();
+        let game = games::registry(None, &Default::default())
+            .unwrap()
+            .remove(0);
+
+        let contents = write_bepinex_cfg(dir.path(), &game, &args).unwrap();
+
+        assert!(contents.contains("## Only displays the specified log levels\nLogLevels = Info\n"));
+        assert_eq!(
+            ini::get_value(&contents, "Logging.Disk", "Enabled"),
+            Some("true")
+        );
+        assert_eq!(
+            ini::get_value(&contents, "Logging.Console", "Enabled"),
+            Some("true")
+        );
+    }
+}
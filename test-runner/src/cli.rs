@@ -1,25 +1,56 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{fmt::Display, path::PathBuf, str::FromStr, time::Duration};
 
 use anyhow::{bail, Context};
-use clap::Parser;
+use clap::{ArgGroup, Parser, Subcommand};
+
+use crate::{
+    display::Resolution,
+    download::{
+        Endpoints, HttpOptions, SourceKind, SymlinkPolicy, BEPINEX_RELEASES_LINK, GITHUB_API_LINK,
+    },
+    game_logs::LogPolicy,
+    ini,
+    launcher::LaunchMode,
+};
+
+const UNITAS_OWNER: &str = "Eddio0141";
+const UNITAS_REPO: &str = "UniTAS";
+const UNITAS_WORKFLOW: &str = "build-on-push.yml";
+const UNITAS_BRANCH: &str = "main";
 
 #[derive(Parser)]
 #[command(version, about)]
+#[command(group(ArgGroup::new("unitas_build").multiple(false)))]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(long)]
+    /// Config file to use instead of `test-runner.toml` found next to this binary or in the repository root
+    pub config: Option<PathBuf>,
+
+    #[arg(long, conflicts_with = "config")]
+    /// Don't load any config file
+    pub no_config: bool,
+
     #[arg(long, default_value_t = 8080)]
     /// Port to use for the TCP connection between this tool and UniTAS
     pub port: u16,
 
-    #[arg(long, requires = "github_token")]
+    #[arg(long)]
     /// Force downloads nightly UniTAS instead of using locally available one
     pub download_unitas: bool,
 
     #[arg(long)]
     /// Github token to use Github APIs to download nightly builds
-    /// If you have `gh` cli tool, `gh auth token` would easily give you a token
-    /// If not set, will try to process in offline mode
+    /// Prefer `GITHUB_TOKEN`/`GH_TOKEN` env vars, a logged in `gh` cli or a token file, which are used if this isn't set
+    /// If no token is found, will try to process in offline mode, an empty token forces offline mode
     pub github_token: Option<String>,
 
+    #[arg(long)]
+    /// File containing the github token, which only the owner can read [default: test-runner/github-token in the user config directory]
+    pub github_token_file: Option<PathBuf>,
+
     #[arg(short, long, value_parser = parse_replace_games, required_if_eq("github_token", ""))]
     /// Replace games to download with local games by name. Example: `2022.3.41f1-base=/home/yuu/local-game`
     /// You can specify multiple --replace-game for different games
@@ -28,10 +59,276 @@ pub struct Args {
     #[arg(long, required_if_eq("github_token", ""))]
     /// If used, BepInEx isn't downloaded and this path is used to replace the download
     pub bepinex_path: Option<PathBuf>,
+
+    #[arg(long, value_parser = parse_bepinex_version, conflicts_with = "bepinex_path")]
+    /// BepInEx version to test with, either `latest`, a release tag like `v5.4.23.2`, or a download URL of a BepInEx zip (like bleeding edge builds from https://builds.bepinex.dev)
+    /// You can specify multiple --bepinex-version to run the tests with each version [default: latest]
+    pub bepinex_version: Vec<BepInExVersion>,
+
+    #[arg(long)]
+    /// Owner of the UniTAS repository to download builds from, for testing builds of a fork [default: Eddio0141]
+    pub unitas_owner: Option<String>,
+
+    #[arg(long)]
+    /// Name of the UniTAS repository to download builds from [default: UniTAS]
+    pub unitas_repo: Option<String>,
+
+    #[arg(long)]
+    /// Workflow file which builds UniTAS [default: build-on-push.yml]
+    pub unitas_workflow: Option<String>,
+
+    #[arg(long, group = "unitas_build")]
+    /// Download the latest successful UniTAS build of this branch [default: main]
+    pub unitas_branch: Option<String>,
+
+    #[arg(long, group = "unitas_build")]
+    /// Download UniTAS from this exact workflow run id
+    pub unitas_run_id: Option<u64>,
+
+    #[arg(long, group = "unitas_build")]
+    /// Download the latest successful UniTAS build of this pull request number
+    pub unitas_pr: Option<u64>,
+
+    #[arg(long, group = "unitas_build")]
+    /// Download the latest successful UniTAS build of this commit, can be a prefix of the SHA
+    pub unitas_sha: Option<String>,
+
+    #[arg(long)]
+    /// Where UniTAS is acquired from: `github-actions`, `github-releases`, `local:<path>` or `mirror:<url>` [default: github-actions]
+    /// Choosing a source always replaces the local UniTAS
+    pub unitas_source: Option<SourceKind>,
+
+    #[arg(
+        long,
+        default_value = "github-releases",
+        conflicts_with = "bepinex_path"
+    )]
+    /// Where BepInEx is acquired from: `github-releases`, `local:<path>` or `mirror:<url>`
+    pub bepinex_source: SourceKind,
+
+    #[arg(long, default_value = "github-actions")]
+    /// Where test games are acquired from: `github-actions`, `local:<path>` or `mirror:<url>`
+    pub games_source: SourceKind,
+
+    #[arg(long, default_value = GITHUB_API_LINK)]
+    /// Base URL of the github api, for github enterprise or a local stand-in server
+    pub github_api_url: String,
+
+    #[arg(long, default_value = BEPINEX_RELEASES_LINK)]
+    /// Github api releases endpoint BepInEx releases are looked up from
+    pub bepinex_release_url: String,
+
+    #[arg(long)]
+    /// Proxy used for all downloads, like `http://proxy.lan:3128`
+    /// If not set, `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` env vars are used
+    pub proxy: Option<String>,
+
+    #[arg(long)]
+    /// PEM file of extra root certificates to trust for downloads, like an internal CA bundle
+    /// You can specify multiple --ca-cert
+    pub ca_cert: Vec<PathBuf>,
+
+    #[arg(long, default_value_t = 30)]
+    /// Seconds to wait for a connection to a download server
+    pub connect_timeout: u64,
+
+    #[arg(long, default_value_t = 60)]
+    /// Seconds to wait for more data from a download server before giving up
+    pub read_timeout: u64,
+
+    #[arg(long)]
+    /// Only run tests of this game, you can specify multiple --test [default: all games for this OS]
+    pub test: Vec<String>,
+
+    #[arg(long, default_value_t = 30)]
+    /// Seconds to wait for UniTAS remote to accept the connection after launching a game
+    pub remote_connect_timeout: u64,
+
+    #[arg(long, default_value_t = 60)]
+    /// Seconds to wait for general tests to finish
+    pub general_tests_timeout: u64,
+
+    #[arg(long, default_value_t = 60)]
+    /// Seconds to wait for a test movie to finish
+    pub movie_timeout: u64,
+
+    #[arg(long)]
+    /// Show games on a display instead of running them with `-batchmode -nographics`, rendered in software. Linux only
+    /// A private Xvfb is started for the display unless `--use-display` is set
+    pub headed: bool,
+
+    #[arg(long, default_value_t = Resolution::default())]
+    /// Screen resolution in headed mode, like `1280x720`
+    pub resolution: Resolution,
+
+    #[arg(long)]
+    /// Use the display from `DISPLAY` in headed mode instead of starting Xvfb
+    pub use_display: bool,
+
+    #[arg(long = "launch", value_enum, default_value_t = LaunchMode::Script)]
+    /// How games are started on linux, either through `run_bepinex.sh` or directly with the Doorstop environment variables it would set
+    pub launch_mode: LaunchMode,
+
+    #[arg(long, allow_hyphen_values = true)]
+    /// Extra argument for every game, after the ones from the game registry. Example: `--game-arg=-force-glcore`
+    /// You can specify multiple --game-arg
+    pub game_arg: Vec<String>,
+
+    #[arg(long, value_parser = parse_env_var)]
+    /// Extra environment variable for every game, overriding the ones from the game registry. Example: `MONO_ENV_OPTIONS=--debug`
+    /// `LD_PRELOAD` is appended to instead, so Doorstop stays preloaded
+    /// You can specify multiple --game-env
+    pub game_env: Vec<(String, String)>,
+
+    #[arg(long)]
+    /// UniTAS.cfg which settings from the config file and the settings the runner needs are merged into
+    pub unitas_cfg: Option<PathBuf>,
+
+    /// UniTAS.cfg settings for every game, from the config file
+    #[arg(skip)]
+    pub unitas_cfg_settings: ini::Settings,
+
+    #[arg(long)]
+    /// BepInEx.cfg to base the managed one on, instead of the one BepInEx wrote in an earlier run
+    pub bepinex_cfg: Option<PathBuf>,
+
+    /// BepInEx.cfg settings for every game, from the config file
+    #[arg(skip)]
+    pub bepinex_cfg_settings: ini::Settings,
+
+    #[arg(long)]
+    /// Log levels BepInEx writes to disk, like `Fatal, Error, Warning, Message, Info` [default: All]
+    pub bepinex_log_levels: Option<String>,
+
+    #[arg(long)]
+    /// Also print stdout and stderr of games, prefixed with the game name. They are always saved with the logs
+    pub tee_game_output: bool,
+
+    #[arg(long)]
+    /// Directory runs write their logs into, each run gets a `<timestamp>-<run id>` directory in it [default: `logs` next to this binary]
+    pub logs_dir: Option<PathBuf>,
+
+    #[arg(long)]
+    /// Keep logs of this many newest runs, counting this one. Old runs are kept forever if neither this nor `--keep-days` is set
+    pub keep_runs: Option<usize>,
+
+    #[arg(long)]
+    /// Keep logs of runs from the last this many days
+    pub keep_days: Option<u64>,
+
+    #[arg(long)]
+    /// Print UniTAS.log and the Unity player log of games as they are written
+    pub tail_logs: bool,
+
+    #[arg(long, default_value_t = 30)]
+    /// Lines of each game log printed when a game times out or crashes
+    pub tail_lines: usize,
+
+    #[arg(long, value_enum, default_value_t = LogPolicy::Fail)]
+    /// What errors and exceptions in game logs do, unless the game lists them in `allowed_log_errors`
+    pub log_errors: LogPolicy,
+
+    #[arg(long)]
+    /// Write test results as JSON to this path
+    pub report_json: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = SymlinkPolicy::Within)]
+    /// How symlinks inside downloaded archives are handled
+    pub symlinks: SymlinkPolicy,
+
+    #[arg(long, default_value = "test-runner.lock")]
+    /// Path to the lockfile which pins exact UniTAS, BepInEx and test game artifacts
+    pub lockfile: PathBuf,
+
+    #[arg(long)]
+    /// Download exactly the artifacts recorded in the lockfile instead of the latest ones
+    pub locked: bool,
+}
+
+#[derive(Subcommand, PartialEq, Eq)]
+pub enum Command {
+    /// Download the latest artifacts and record them in the lockfile, without running tests
+    Update,
+    /// Play the benchmark movies of games and compare their frame times against a baseline
+    Bench(BenchArgs),
+}
+
+#[derive(clap::Args, PartialEq, Eq)]
+pub struct BenchArgs {
+    #[arg(long, default_value_t = 5)]
+    /// Times each benchmark movie is played
+    pub runs: u32,
+
+    #[arg(long, default_value = "bench-baseline.json")]
+    /// Results of an earlier build to compare against
+    pub baseline: PathBuf,
+
+    #[arg(long)]
+    /// Write the results into the baseline, keeping benchmarks which weren't run
+    pub save_baseline: bool,
+
+    #[arg(long, default_value_t = 10)]
+    /// Largest drop of mean frames per second, in percent, which isn't a regression
+    pub max_fps_drop: u32,
+
+    #[arg(long, default_value_t = 10)]
+    /// Largest increase of the 95th percentile of the frame time of each run, in percent, which isn't a regression
+    pub max_frame_time_increase: u32,
 }
 
 impl Args {
-    pub fn validate(&self) -> anyhow::Result<()> {
+    /// Checks options against each other, `has_token` being if a github token was found
+    pub fn validate(&self, has_token: bool) -> anyhow::Result<()> {
+        if self.command == Some(Command::Update) {
+            if self.locked {
+                bail!("`--locked` can't be used while updating the lockfile");
+            }
+            if !has_token {
+                bail!("updating the lockfile requires a github token to download nightly builds");
+            }
+        }
+
+        if !has_token {
+            if self.download_unitas {
+                bail!("`--download-unitas` requires a github token to download nightly builds");
+            }
+
+            let uses_actions = self
+                .unitas_source
+                .as_ref()
+                .is_none_or(|source| matches!(source, SourceKind::GithubActions));
+            if uses_actions && self.selects_unitas_build() {
+                bail!("options selecting a UniTAS build require a github token to download nightly builds");
+            }
+
+            // without a token the pinned builds would silently be swapped for whatever is on disk
+            let games_use_actions = matches!(self.games_source, SourceKind::GithubActions);
+            if self.locked && (uses_actions || games_use_actions) {
+                bail!("`--locked` requires a github token to download the builds pinned from github actions");
+            }
+        }
+
+        for (name, url) in [
+            ("--github-api-url", &self.github_api_url),
+            ("--bepinex-release-url", &self.bepinex_release_url),
+        ] {
+            reqwest::Url::parse(url).with_context(|| format!("invalid `{name}` `{url}`"))?;
+        }
+
+        if let Some(Command::Bench(bench)) = &self.command {
+            if bench.runs == 0 {
+                bail!("benchmarks need at least 1 run");
+            }
+        }
+
+        if self.locked && self.selects_unitas_build() {
+            bail!("`--locked` can't be used with options selecting a UniTAS build, the build is pinned by the lockfile");
+        }
+
+        if self.locked && !self.bepinex_version.is_empty() {
+            bail!("`--locked` can't be used with `--bepinex-version`, the versions are pinned by the lockfile");
+        }
+
         for (i, replace) in self.replace_game.iter().enumerate() {
             if self
                 .replace_game
@@ -46,8 +343,90 @@ impl Args {
             }
         }
 
+        if self.unitas_source.is_some() && self.selects_unitas_build() {
+            let Some(SourceKind::GithubActions) = self.unitas_source else {
+                bail!("options selecting a UniTAS build can only be used with the `github-actions` UniTAS source");
+            };
+        }
+
         Ok(())
     }
+
+    /// If any UniTAS build selecting option is used, which means UniTAS must be downloaded
+    pub fn selects_unitas_build(&self) -> bool {
+        self.unitas_owner.is_some()
+            || self.unitas_repo.is_some()
+            || self.unitas_workflow.is_some()
+            || self.unitas_branch.is_some()
+            || self.unitas_run_id.is_some()
+            || self.unitas_pr.is_some()
+            || self.unitas_sha.is_some()
+    }
+
+    pub fn endpoints(&self) -> Endpoints {
+        Endpoints {
+            github_api: self.github_api_url.trim_end_matches('/').to_string(),
+            bepinex_releases: self.bepinex_release_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn http_options(&self) -> HttpOptions {
+        HttpOptions {
+            proxy: self.proxy.to_owned(),
+            ca_certs: self.ca_cert.to_owned(),
+            connect_timeout: Duration::from_secs(self.connect_timeout),
+            read_timeout: Duration::from_secs(self.read_timeout),
+        }
+    }
+
+    pub fn unitas_workflow(&self) -> GithubWorkflow {
+        let build = if let Some(id) = self.unitas_run_id {
+            WorkflowBuild::RunId(id)
+        } else if let Some(number) = self.unitas_pr {
+            WorkflowBuild::PullRequest(number)
+        } else if let Some(sha) = &self.unitas_sha {
+            WorkflowBuild::Commit(sha.to_owned())
+        } else {
+            WorkflowBuild::Branch(
+                self.unitas_branch
+                    .to_owned()
+                    .unwrap_or_else(|| UNITAS_BRANCH.to_string()),
+            )
+        };
+
+        GithubWorkflow {
+            owner: self
+                .unitas_owner
+                .to_owned()
+                .unwrap_or_else(|| UNITAS_OWNER.to_string()),
+            repo: self
+                .unitas_repo
+                .to_owned()
+                .unwrap_or_else(|| UNITAS_REPO.to_string()),
+            workflow: self
+                .unitas_workflow
+                .to_owned()
+                .unwrap_or_else(|| UNITAS_WORKFLOW.to_string()),
+            build,
+        }
+    }
+}
+
+/// Github actions workflow which builds are downloaded from
+#[derive(Clone)]
+pub struct GithubWorkflow {
+    pub owner: String,
+    pub repo: String,
+    pub workflow: String,
+    pub build: WorkflowBuild,
+}
+
+#[derive(Clone)]
+pub enum WorkflowBuild {
+    Branch(String),
+    RunId(u64),
+    PullRequest(u64),
+    Commit(String),
 }
 
 #[derive(Clone)]
@@ -56,6 +435,17 @@ pub struct ReplaceGame {
     pub game_path: PathBuf,
 }
 
+fn parse_env_var(str: &str) -> Result<(String, String), anyhow::Error> {
+    let Some((key, value)) = str.split_once('=') else {
+        bail!("expected pattern of `KEY=value`")
+    };
+    if key.is_empty() {
+        bail!("environment variable name can't be empty");
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
 fn parse_replace_games(str: &str) -> Result<ReplaceGame, anyhow::Error> {
     let Some((name, game_path)) = str.split_once('=') else {
         bail!("expected pattern of `game-name=path`")
@@ -75,3 +465,70 @@ fn parse_replace_games(str: &str) -> Result<ReplaceGame, anyhow::Error> {
         game_path,
     })
 }
+
+#[derive(Clone)]
+pub enum BepInExVersion {
+    Latest,
+    Tag(String),
+    Url(String),
+}
+
+impl Display for BepInExVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BepInExVersion::Latest => write!(f, "latest"),
+            BepInExVersion::Tag(tag) => write!(f, "{tag}"),
+            BepInExVersion::Url(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+pub fn parse_bepinex_version(str: &str) -> Result<BepInExVersion, anyhow::Error> {
+    if str.is_empty() {
+        bail!("BepInEx version can't be empty");
+    }
+
+    let version = if str == "latest" {
+        BepInExVersion::Latest
+    } else if str.starts_with("http://") || str.starts_with("https://") {
+        BepInExVersion::Url(str.to_string())
+    } else {
+        BepInExVersion::Tag(str.to_string())
+    };
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(cli: &[&str], has_token: bool) -> anyhow::Result<()> {
+        Args::parse_from(["test-runner"].iter().chain(cli)).validate(has_token)
+    }
+
+    #[test]
+    fn urls_are_validated() {
+        assert!(validate(&["--github-api-url", "api.github.com"], true).is_err());
+        assert!(validate(&["--bepinex-release-url", "http://"], true).is_err());
+        assert!(validate(&["--github-api-url", "http://localhost:8000/api"], true).is_ok());
+    }
+
+    #[test]
+    fn locked_needs_token_for_github_actions() {
+        assert!(validate(&["--locked"], false).is_err());
+        assert!(validate(&["--locked", "--unitas-source", "local:unitas"], false).is_err());
+        assert!(validate(&["--locked"], true).is_ok());
+        assert!(validate(
+            &[
+                "--locked",
+                "--unitas-source",
+                "local:unitas",
+                "--games-source",
+                "local:games"
+            ],
+            false
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,531 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{parser::ValueSource, ArgMatches};
+use serde::Deserialize;
+
+use crate::{
+    cli::{parse_bepinex_version, Args, Command, GithubWorkflow, ReplaceGame, WorkflowBuild},
+    display::Resolution,
+    download::{SourceKind, SymlinkPolicy},
+    game_logs::LogPolicy,
+    games::GameEntry,
+    ini,
+    launcher::LaunchMode,
+    Os,
+};
+
+pub const CONFIG_FILE_NAME: &str = "test-runner.toml";
+
+const TEST_GAMES_OWNER: &str = "Eddio0141";
+const TEST_GAMES_REPO: &str = "UniTASTestClients";
+const TEST_GAMES_WORKFLOW: &str = "build-on-push.yml";
+const TEST_GAMES_BRANCH: &str = "main";
+
+/// Project configuration from `test-runner.toml`, flags given on the command line take priority over it
+///
+/// Relative paths are relative to the directory of the config file
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    port: Option<u16>,
+    lockfile: Option<PathBuf>,
+    symlinks: Option<SymlinkPolicy>,
+    sources: SourcesConfig,
+    unitas: WorkflowConfig,
+    test_games: WorkflowConfig,
+    bepinex: BepInExConfig,
+    github: GithubConfig,
+    http: HttpConfig,
+    timeouts: TimeoutsConfig,
+    display: DisplayConfig,
+    launch: LaunchConfig,
+    unitas_cfg: CfgFileConfig,
+    bepinex_cfg: CfgFileConfig,
+    logs: LogsConfig,
+    report: ReportConfig,
+    bench: BenchConfig,
+    tests: TestsConfig,
+    /// Directory of test game unity projects, to discover games from [default: `Clients/` in the repository root]
+    clients_dir: Option<PathBuf>,
+    /// Test games by name, added to the registry
+    games: BTreeMap<String, GameEntry>,
+
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct SourcesConfig {
+    unitas: Option<Parsed<SourceKind>>,
+    bepinex: Option<Parsed<SourceKind>>,
+    games: Option<Parsed<SourceKind>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct WorkflowConfig {
+    owner: Option<String>,
+    repo: Option<String>,
+    workflow: Option<String>,
+    branch: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct BepInExConfig {
+    versions: Vec<String>,
+    release_url: Option<String>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct GithubConfig {
+    api_url: Option<String>,
+    token_file: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct HttpConfig {
+    proxy: Option<String>,
+    ca_certs: Vec<PathBuf>,
+    /// Seconds
+    connect_timeout: Option<u64>,
+    /// Seconds
+    read_timeout: Option<u64>,
+}
+
+/// All in seconds
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TimeoutsConfig {
+    remote_connect: Option<u64>,
+    general_tests: Option<u64>,
+    movie: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DisplayConfig {
+    headed: Option<bool>,
+    resolution: Option<Parsed<Resolution>>,
+    use_existing: Option<bool>,
+}
+
+/// How every game is launched
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct LaunchConfig {
+    mode: Option<LaunchMode>,
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
+    /// Print game stdout and stderr
+    tee_output: Option<bool>,
+}
+
+/// Config file of a plugin, merged into `base` if given
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct CfgFileConfig {
+    base: Option<PathBuf>,
+    /// Values by section, then key. Sections with dots need quotes, like `[bepinex_cfg.settings."Logging.Disk"]`
+    settings: ini::Settings,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct LogsConfig {
+    dir: Option<PathBuf>,
+    keep_runs: Option<usize>,
+    keep_days: Option<u64>,
+    /// Print game logs as they are written
+    tail: Option<bool>,
+    tail_lines: Option<usize>,
+    /// What unexpected errors in game logs do
+    errors: Option<LogPolicy>,
+}
+
+/// Used by the `bench` command
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct BenchConfig {
+    runs: Option<u32>,
+    baseline: Option<PathBuf>,
+    /// Percent
+    max_fps_drop: Option<u32>,
+    /// Percent
+    max_frame_time_increase: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ReportConfig {
+    json: Option<PathBuf>,
+}
+
+/// Names of games to test on each OS, all games if empty
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TestsConfig {
+    linux: Vec<String>,
+    windows: Vec<String>,
+}
+
+/// Value parsed the same way as its command line flag
+#[derive(Deserialize)]
+#[serde(
+    try_from = "String",
+    bound(deserialize = "T: FromStr, T::Err: Display")
+)]
+struct Parsed<T>(T);
+
+impl<T> TryFrom<String> for Parsed<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value
+            .parse()
+            .map(Parsed)
+            .map_err(|err| format!("invalid value `{value}`: {err}"))
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config at `{}`", path.display()))?;
+        let mut config: Self = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse config at `{}`", path.display()))?;
+
+        config.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        Ok(config)
+    }
+
+    /// Finds `test-runner.toml` next to the binary, or in the root of the git repository the current directory is in
+    pub fn discover(exe_dir: &Path) -> Option<PathBuf> {
+        let next_to_exe = exe_dir.join(CONFIG_FILE_NAME);
+        if next_to_exe.is_file() {
+            return Some(next_to_exe);
+        }
+
+        let cwd = env::current_dir().ok()?;
+        let repo_root = cwd.ancestors().find(|dir| dir.join(".git").exists())?;
+        let in_repo_root = repo_root.join(CONFIG_FILE_NAME);
+
+        in_repo_root.is_file().then_some(in_repo_root)
+    }
+
+    /// Fills in options which weren't given on the command line
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches, os: &Os) -> Result<()> {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        let path = |path: &PathBuf| self.dir.join(path);
+        let source = |source: &Option<Parsed<SourceKind>>| {
+            source.as_ref().map(|Parsed(kind)| match kind {
+                SourceKind::Local(root) => SourceKind::Local(path(root)),
+                kind => kind.clone(),
+            })
+        };
+
+        macro_rules! set {
+            ($id:ident, $value:expr) => {
+                if !from_cli(stringify!($id)) {
+                    if let Some(value) = $value {
+                        args.$id = value;
+                    }
+                }
+            };
+        }
+        macro_rules! set_opt {
+            ($id:ident, $value:expr) => {
+                if !from_cli(stringify!($id)) {
+                    if let Some(value) = $value {
+                        args.$id = Some(value);
+                    }
+                }
+            };
+        }
+
+        set!(port, self.port);
+        set!(lockfile, self.lockfile.as_ref().map(path));
+        set!(symlinks, self.symlinks);
+
+        set_opt!(unitas_source, source(&self.sources.unitas));
+        set!(bepinex_source, source(&self.sources.bepinex));
+        set!(games_source, source(&self.sources.games));
+
+        if !from_cli("bepinex_version") && !from_cli("bepinex_path") && !args.locked {
+            args.bepinex_version = self
+                .bepinex
+                .versions
+                .iter()
+                .map(|version| parse_bepinex_version(version))
+                .collect::<Result<_>>()
+                .context("invalid BepInEx version in config")?;
+        }
+        set!(bepinex_release_url, self.bepinex.release_url.to_owned());
+        if !from_cli("bepinex_version") && !from_cli("bepinex_source") {
+            set_opt!(bepinex_path, self.bepinex.path.as_ref().map(path));
+        }
+        // clap only sees the command line, so the config can still set both
+        if args.bepinex_path.is_some() && !args.bepinex_version.is_empty() {
+            bail!("the argument '--bepinex-path <BEPINEX_PATH>' cannot be used with '--bepinex-version <BEPINEX_VERSION>'");
+        }
+
+        set!(github_api_url, self.github.api_url.to_owned());
+        set_opt!(github_token_file, self.github.token_file.as_ref().map(path));
+
+        set_opt!(proxy, self.http.proxy.to_owned());
+        if !from_cli("ca_cert") {
+            args.ca_cert = self.http.ca_certs.iter().map(path).collect();
+        }
+        set!(connect_timeout, self.http.connect_timeout);
+        set!(read_timeout, self.http.read_timeout);
+
+        set!(remote_connect_timeout, self.timeouts.remote_connect);
+        set!(general_tests_timeout, self.timeouts.general_tests);
+        set!(movie_timeout, self.timeouts.movie);
+
+        set!(headed, self.display.headed);
+        set!(
+            resolution,
+            self.display.resolution.as_ref().map(|Parsed(res)| *res)
+        );
+        set!(use_display, self.display.use_existing);
+
+        set!(launch_mode, self.launch.mode);
+        if !from_cli("game_arg") {
+            args.game_arg = self.launch.args.to_owned();
+        }
+        if !from_cli("game_env") {
+            args.game_env = self.launch.env.clone().into_iter().collect();
+        }
+        set!(tee_game_output, self.launch.tee_output);
+
+        set_opt!(unitas_cfg, self.unitas_cfg.base.as_ref().map(path));
+        args.unitas_cfg_settings = self.unitas_cfg.settings.clone();
+        set_opt!(bepinex_cfg, self.bepinex_cfg.base.as_ref().map(path));
+        args.bepinex_cfg_settings = self.bepinex_cfg.settings.clone();
+
+        set_opt!(logs_dir, self.logs.dir.as_ref().map(path));
+        set_opt!(keep_runs, self.logs.keep_runs);
+        set_opt!(keep_days, self.logs.keep_days);
+        set!(tail_logs, self.logs.tail);
+        set!(tail_lines, self.logs.tail_lines);
+        set!(log_errors, self.logs.errors);
+
+        set_opt!(report_json, self.report.json.as_ref().map(path));
+
+        if let (Some(Command::Bench(bench)), Some(bench_matches)) =
+            (&mut args.command, matches.subcommand_matches("bench"))
+        {
+            let from_cli =
+                |id: &str| bench_matches.value_source(id) == Some(ValueSource::CommandLine);
+            macro_rules! set_bench {
+                ($id:ident, $value:expr) => {
+                    if !from_cli(stringify!($id)) {
+                        if let Some(value) = $value {
+                            bench.$id = value;
+                        }
+                    }
+                };
+            }
+
+            set_bench!(runs, self.bench.runs);
+            set_bench!(baseline, self.bench.baseline.as_ref().map(path));
+            set_bench!(max_fps_drop, self.bench.max_fps_drop);
+            set_bench!(max_frame_time_increase, self.bench.max_frame_time_increase);
+        }
+
+        if !from_cli("test") {
+            args.test = match os {
+                Os::Linux => self.tests.linux.to_owned(),
+                Os::Windows => self.tests.windows.to_owned(),
+            };
+        }
+
+        // local games from command line replace the same game from config
+        for (name, game) in &self.games {
+            let Some(game_path) = &game.path else {
+                continue;
+            };
+            if args
+                .replace_game
+                .iter()
+                .any(|replace| &replace.name == name)
+            {
+                continue;
+            }
+
+            let game_path = path(game_path);
+            if !game_path.is_dir() {
+                bail!(
+                    "path of game `{name}` in config, `{}` is not a directory",
+                    game_path.display()
+                );
+            }
+
+            args.replace_game.push(ReplaceGame {
+                name: name.to_owned(),
+                game_path,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn clients_dir(&self) -> Option<PathBuf> {
+        self.clients_dir.as_ref().map(|dir| self.dir.join(dir))
+    }
+
+    pub fn games(&self) -> &BTreeMap<String, GameEntry> {
+        &self.games
+    }
+
+    /// Workflow UniTAS is downloaded from, options from the command line take priority
+    ///
+    /// Unlike the command line options, these don't force downloading UniTAS
+    pub fn unitas_workflow(&self, args: &Args) -> GithubWorkflow {
+        let mut workflow = args.unitas_workflow();
+
+        if args.unitas_owner.is_none() {
+            if let Some(owner) = &self.unitas.owner {
+                workflow.owner = owner.to_owned();
+            }
+        }
+        if args.unitas_repo.is_none() {
+            if let Some(repo) = &self.unitas.repo {
+                workflow.repo = repo.to_owned();
+            }
+        }
+        if args.unitas_workflow.is_none() {
+            if let Some(file) = &self.unitas.workflow {
+                workflow.workflow = file.to_owned();
+            }
+        }
+        if !args.selects_unitas_build() {
+            if let Some(branch) = &self.unitas.branch {
+                workflow.build = WorkflowBuild::Branch(branch.to_owned());
+            }
+        }
+
+        workflow
+    }
+
+    /// Workflow which builds the test games
+    pub fn test_games_workflow(&self) -> GithubWorkflow {
+        let WorkflowConfig {
+            owner,
+            repo,
+            workflow,
+            branch,
+        } = &self.test_games;
+
+        GithubWorkflow {
+            owner: owner.as_deref().unwrap_or(TEST_GAMES_OWNER).to_string(),
+            repo: repo.as_deref().unwrap_or(TEST_GAMES_REPO).to_string(),
+            workflow: workflow
+                .as_deref()
+                .unwrap_or(TEST_GAMES_WORKFLOW)
+                .to_string(),
+            build: WorkflowBuild::Branch(
+                branch.as_deref().unwrap_or(TEST_GAMES_BRANCH).to_string(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, FromArgMatches};
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+port = 9000
+lockfile = "locks/test-runner.lock"
+
+[sources]
+games = "local:games"
+
+[bepinex]
+versions = ["v5.4.23.2", "latest"]
+
+[tests]
+linux = ["unity_latest"]
+windows = ["unity_latest", "2022.3.41f1-base"]
+"#;
+
+    fn apply(cli: &[&str]) -> Args {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+        config.dir = PathBuf::from("/project");
+
+        let matches = Args::command()
+            .try_get_matches_from(["test-runner"].iter().chain(cli))
+            .unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        config.apply(&mut args, &matches, &Os::Windows).unwrap();
+        args
+    }
+
+    #[test]
+    fn config_fills_defaults() {
+        let args = apply(&[]);
+
+        assert_eq!(args.port, 9000);
+        assert_eq!(args.lockfile, Path::new("/project/locks/test-runner.lock"));
+        assert!(
+            matches!(&args.games_source, SourceKind::Local(path) if path == Path::new("/project/games"))
+        );
+        assert_eq!(args.bepinex_version.len(), 2);
+        assert_eq!(args.test, ["unity_latest", "2022.3.41f1-base"]);
+    }
+
+    #[test]
+    fn cli_overrides_config() {
+        let args = apply(&[
+            "--port",
+            "8080",
+            "--games-source",
+            "github-actions",
+            "--bepinex-version",
+            "latest",
+            "--test",
+            "unity_latest",
+        ]);
+
+        assert_eq!(args.port, 8080);
+        assert!(matches!(args.games_source, SourceKind::GithubActions));
+        assert_eq!(args.bepinex_version.len(), 1);
+        assert_eq!(args.test, ["unity_latest"]);
+    }
+
+    #[test]
+    fn bepinex_path_conflicts_with_versions() {
+        let config = CONFIG.replace("[bepinex]\n", "[bepinex]\npath = \"bepinex\"\n");
+        let mut config: Config = toml::from_str(&config).unwrap();
+        config.dir = PathBuf::from("/project");
+        let matches = Args::command().get_matches_from(["test-runner"]);
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+
+        assert!(config.apply(&mut args, &matches, &Os::Windows).is_err());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("prot = 9000").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    env,
+    fmt::Display,
+    path::Path,
+    process::{Child, Command, Stdio},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+
+const XVFB_BIN: &str = "Xvfb";
+/// Display numbers to try for a private Xvfb, away from the ones desktops usually take
+const XVFB_DISPLAYS: std::ops::Range<u32> = 99..200;
+const XVFB_START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Screen size as `<width>x<height>`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+        }
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once(['x', 'X'])
+            .context("resolution must be formatted as `<width>x<height>`, like `1280x720`")?;
+        let width = width.trim().parse().context("invalid resolution width")?;
+        let height = height.trim().parse().context("invalid resolution height")?;
+        if width == 0 || height == 0 {
+            bail!("resolution can't be zero");
+        }
+
+        Ok(Self { width, height })
+    }
+}
+
+impl Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// X display games are shown on in headed mode, a private Xvfb is stopped once this is dropped
+pub struct X11Display {
+    name: String,
+    xvfb: Option<Child>,
+}
+
+impl X11Display {
+    /// The display from `DISPLAY`
+    pub fn existing() -> Result<Self> {
+        let name = env::var("DISPLAY")
+            .ok()
+            .filter(|display| !display.is_empty())
+            .context("`DISPLAY` isn't set, there is no existing display to use")?;
+
+        Ok(Self { name, xvfb: None })
+    }
+
+    /// Starts Xvfb on a free display number
+    pub fn start_xvfb(resolution: Resolution) -> Result<Self> {
+        let number = XVFB_DISPLAYS
+            .clone()
+            .find(|n| !display_in_use(*n))
+            .context("failed to find a free display number for Xvfb")?;
+        let name = format!(":{number}");
+
+        debug!("starting Xvfb on display `{name}` with resolution {resolution}");
+        let xvfb = Command::new(XVFB_BIN)
+            .arg(&name)
+            .args(["-screen", "0", &format!("{resolution}x24")])
+            .args(["-nolisten", "tcp"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to start `{XVFB_BIN}`, is it installed?"))?;
+        let mut display = Self {
+            name,
+            xvfb: Some(xvfb),
+        };
+
+        // ready once the server socket is there
+        let socket = x11_socket(number);
+        let mut waited = Duration::ZERO;
+        while !Path::new(&socket).exists() {
+            let xvfb = display.xvfb.as_mut().unwrap();
+            if let Some(status) = xvfb.try_wait().context("failed to check on Xvfb")? {
+                bail!("Xvfb exited with {status} before the display was ready");
+            }
+            if waited >= XVFB_START_TIMEOUT {
+                bail!(
+                    "Xvfb didn't create display `{}` after {} seconds",
+                    display.name,
+                    XVFB_START_TIMEOUT.as_secs()
+                );
+            }
+
+            let step = Duration::from_millis(100);
+            thread::sleep(step);
+            waited += step;
+        }
+
+        Ok(display)
+    }
+
+    /// Value for `DISPLAY` of the game
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Drop for X11Display {
+    fn drop(&mut self) {
+        if let Some(xvfb) = &mut self.xvfb {
+            let _ = xvfb.kill();
+            let _ = xvfb.wait();
+        }
+    }
+}
+
+fn x11_socket(number: u32) -> String {
+    format!("/tmp/.X11-unix/X{number}")
+}
+
+fn display_in_use(number: u32) -> bool {
+    Path::new(&x11_socket(number)).exists() || Path::new(&format!("/tmp/.X{number}-lock")).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_resolution() {
+        assert_eq!(
+            "1920x1080".parse::<Resolution>().unwrap(),
+            Resolution {
+                width: 1920,
+                height: 1080
+            }
+        );
+        assert!("1920".parse::<Resolution>().is_err());
+        assert!("0x1080".parse::<Resolution>().is_err());
+        assert!("widexhigh".parse::<Resolution>().is_err());
+    }
+}
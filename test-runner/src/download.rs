@@ -1,259 +1,323 @@
-#[cfg(target_family = "unix")]
-use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
-use std::{fmt::Write, io::Cursor, path::Path};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Context;
 use anyhow::Result;
-use gh_api::Artifact;
-use gh_api::ArtifactFilter;
+use anyhow::{bail, Context};
+use const_format::formatcp;
 use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
-use regex::Regex;
-use serde_json::Value;
+use log::warn;
+use reqwest::{Certificate, Client, Proxy, Response};
 use tokio::{
     fs,
     task::{self, JoinSet},
 };
 use tokio_stream::StreamExt;
-use zip::ZipArchive;
 
-use crate::cli::ReplaceGame;
+use crate::cli::{BepInExVersion, GithubWorkflow, ReplaceGame};
 use crate::fs_utils;
-use crate::UNIX_UNITY_EXE_NAME;
+use crate::games::Backend;
+use crate::lockfile::{sha256_hex, verify_sha256, LockedArtifact, LockedRelease};
 use crate::{Arch, Os};
 
+mod archive;
 mod gh_api;
+mod source;
+#[cfg(test)]
+mod test_server;
+
+use archive::extract_zip;
+pub use archive::SymlinkPolicy;
+use gh_api::GhApi;
+use source::{
+    fetch_url, ArtifactQuery, ArtifactSource, Fetched, GithubActions, GithubReleases, HttpMirror,
+    LocalDir, Location, ResolvedArtifact,
+};
+pub use source::{Source, SourceKind};
+
+pub use gh_api::GITHUB_API_LINK;
+pub const BEPINEX_RELEASES_LINK: &str =
+    formatcp!("{GITHUB_API_LINK}/repos/BepInEx/BepInEx/releases");
+
+/// Base URLs artifacts are downloaded from, which can point to a local server for testing
+#[derive(Clone)]
+pub struct Endpoints {
+    /// Base URL of the github api
+    pub github_api: String,
+    /// Releases endpoint of the BepInEx repository
+    pub bepinex_releases: String,
+}
+
+/// Source for UniTAS, or `None` if github actions is used without a token, which means offline mode
+pub fn unitas_source(
+    kind: &SourceKind,
+    endpoints: &Endpoints,
+    client: &Client,
+    workflow: GithubWorkflow,
+    gh_token: Option<String>,
+) -> Option<Source> {
+    let source = match kind {
+        SourceKind::GithubActions => Source::GithubActions(GithubActions {
+            api: gh_api(endpoints, client),
+            workflow,
+            token: gh_token?,
+        }),
+        SourceKind::GithubReleases => Source::GithubReleases(GithubReleases {
+            client: client.clone(),
+            releases_url: gh_api::releases_url(
+                &gh_api(endpoints, client),
+                &workflow.owner,
+                &workflow.repo,
+            ),
+        }),
+        SourceKind::Local(root) => Source::Local(LocalDir { root: root.clone() }),
+        SourceKind::Mirror(base_url) => Source::Mirror(HttpMirror {
+            client: client.clone(),
+            base_url: base_url.clone(),
+        }),
+    };
+
+    Some(source)
+}
+
+pub fn bepinex_source(kind: &SourceKind, endpoints: &Endpoints, client: &Client) -> Result<Source> {
+    let source = match kind {
+        SourceKind::GithubActions => bail!("BepInEx can't be downloaded from github actions"),
+        SourceKind::GithubReleases => Source::GithubReleases(GithubReleases {
+            client: client.clone(),
+            releases_url: endpoints.bepinex_releases.to_owned(),
+        }),
+        SourceKind::Local(root) => Source::Local(LocalDir { root: root.clone() }),
+        SourceKind::Mirror(base_url) => Source::Mirror(HttpMirror {
+            client: client.clone(),
+            base_url: base_url.clone(),
+        }),
+    };
+
+    Ok(source)
+}
+
+/// Source for test games, or `None` if github actions is used without a token, which means offline mode
+pub fn games_source(
+    kind: &SourceKind,
+    endpoints: &Endpoints,
+    client: &Client,
+    workflow: GithubWorkflow,
+    gh_token: Option<String>,
+) -> Result<Option<Source>> {
+    let source = match kind {
+        SourceKind::GithubActions => {
+            let Some(token) = gh_token else {
+                return Ok(None);
+            };
+
+            Source::GithubActions(GithubActions {
+                api: gh_api(endpoints, client),
+                workflow,
+                token,
+            })
+        }
+        SourceKind::GithubReleases => bail!("test games can't be downloaded from github releases"),
+        SourceKind::Local(root) => Source::Local(LocalDir { root: root.clone() }),
+        SourceKind::Mirror(base_url) => Source::Mirror(HttpMirror {
+            client: client.clone(),
+            base_url: base_url.clone(),
+        }),
+    };
+
+    Ok(Some(source))
+}
+
+fn gh_api(endpoints: &Endpoints, client: &Client) -> GhApi {
+    GhApi {
+        client: client.clone(),
+        base_url: endpoints.github_api.to_owned(),
+    }
+}
+
+/// Settings of the http client shared by all downloads
+pub struct HttpOptions {
+    /// Proxy for all requests, otherwise proxies from `HTTP_PROXY`, `HTTPS_PROXY` and `ALL_PROXY` are used
+    pub proxy: Option<String>,
+    /// PEM files of extra root certificates to trust
+    pub ca_certs: Vec<PathBuf>,
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+}
+
+pub fn http_client(options: &HttpOptions) -> Result<Client> {
+    // github requires us to have User-Agent header
+    let mut builder = Client::builder()
+        .user_agent(env!("CARGO_PKG_NAME"))
+        .connect_timeout(options.connect_timeout)
+        .read_timeout(options.read_timeout);
+
+    if let Some(proxy) = &options.proxy {
+        let proxy =
+            Proxy::all(proxy).with_context(|| format!("failed to parse proxy `{proxy}`"))?;
+        builder = builder.proxy(proxy);
+    }
+
+    for path in &options.ca_certs {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("failed to read CA certificate `{}`", path.display()))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("failed to parse CA certificate `{}`", path.display()))?;
+        if certs.is_empty() {
+            bail!("no certificates found in `{}`", path.display());
+        }
+
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder.build().context("failed to create http client")
+}
 
 pub async fn dl_unitas(
     unitas_dir: &Path,
     download_unitas: bool,
+    source: Option<Source>,
     pb: MultiProgress,
-    gh_token: Option<String>,
-) -> Result<()> {
-    let Some(gh_token) = gh_token else {
+    locked: Option<LockedArtifact>,
+    symlinks: SymlinkPolicy,
+) -> Result<Option<LockedArtifact>> {
+    let Some(source) = source else {
         if !unitas_dir.is_dir() {
-            panic!(
-                "failed to find UniTAS directory at {}",
+            bail!(
+                "failed to find UniTAS directory at `{}`, a github token is needed to download it",
                 unitas_dir.display()
             );
         }
-        return Ok(());
+        return Ok(None);
     };
 
     if unitas_dir.is_dir() {
-        if !download_unitas {
-            return Ok(());
+        // a pinned UniTAS always replaces the local one
+        if !download_unitas && locked.is_none() {
+            return Ok(None);
         }
 
         // delete directory
-        fs::remove_dir_all(unitas_dir)
-            .await
-            .expect("failed to remove existing UniTAS directory");
+        fs::remove_dir_all(unitas_dir).await.with_context(|| {
+            format!(
+                "failed to remove existing UniTAS directory `{}`",
+                unitas_dir.display()
+            )
+        })?;
     }
 
-    let artifact = gh_api::latest_artifacts(
-        "Eddio0141",
-        "UniTAS",
-        &gh_token,
-        "build-on-push.yml",
-        "main",
-        Some(ArtifactFilter::TargetName("UniTAS")),
-    )
-    .await
-    .context("failed to get latest build of UniTAS")?;
-
-    let artifact = artifact
-        .first()
-        .context("failed to get download link for UniTAS")?;
-
-    let Artifact { link, dl_len, .. } = artifact;
-    let dl_len = *dl_len;
-
-    let response = gh_api::gh_api_client(link, &gh_token)
-        .await
-        .context("failed to get download client for downloading UniTAS")?
-        .send()
-        .await
-        .context("failed to send request to download UniTAS")?;
-    let mut bytes = response.bytes_stream();
-
-    let mut dl_buff = Vec::with_capacity(dl_len as usize);
-
-    let pb = pb.add(dl_progress_bar(dl_len));
-    pb.set_message("downloading UniTAS");
-
-    while let Some(chunk) = bytes.next().await {
-        let chunk = chunk.expect("failed to get chunk for UniTAS download");
-        for byte in chunk {
-            dl_buff.push(byte);
+    let artifact = match (&source, &locked) {
+        (Source::GithubActions(actions), Some(locked)) => {
+            ResolvedArtifact::from_locked_artifact(locked, &actions.api)
         }
+        _ => source
+            .resolve(&ArtifactQuery::UniTas)
+            .await?
+            .into_iter()
+            .next()
+            .context("failed to get download link for UniTAS")?,
+    };
 
-        pb.set_position(dl_buff.len() as u64);
-    }
-
-    pb.finish_with_message("downloaded UniTAS");
-
-    fs::create_dir_all(unitas_dir)
-        .await
-        .expect("failed to create directory for unitas");
-
-    let mut archive =
-        ZipArchive::new(Cursor::new(dl_buff)).expect("failed to load unitas as zip archive");
+    let fetched = source.fetch(&artifact, &pb).await?;
+    let expected_sha256 = locked.as_ref().map(|locked| locked.sha256.as_str());
+    let installed = install(fetched, unitas_dir, expected_sha256, "UniTAS", symlinks).await?;
 
-    let unitas_dir = unitas_dir.to_path_buf();
-    task::spawn_blocking(move || {
-        archive
-            .extract(unitas_dir)
-            .expect("failed to extract unitas");
-    })
-    .await
-    .unwrap();
+    Ok(installed.and_then(|installed| lock_artifact(&artifact, &installed)))
+}
 
-    Ok(())
+/// BepInEx release to download
+#[derive(Clone)]
+pub enum BepInExRelease {
+    Version(BepInExVersion),
+    Locked(LockedRelease),
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn dl_bepinex(
     dl_dir: &Path,
     os: &Os,
     arch: &Arch,
+    backend: Backend,
     pb: MultiProgress,
-    bepinex_path: Option<PathBuf>,
-) {
-    if let Some(bepinex_path) = bepinex_path {
-        fs_utils::copy_dir_all(bepinex_path, dl_dir)
-            .await
-            .expect("failed to copy BepInEx directory to destination");
-        return;
-    }
-
-    let url = "https://api.github.com/repos/BepInEx/BepInEx/releases/latest";
-
-    // github requires us to have User-Agent header
-    let client = reqwest::Client::builder()
-        .user_agent(env!("CARGO_PKG_NAME"))
-        .build()
-        .expect("failed to create reqwest client");
-
-    let response = client
-        .get(url)
-        .send()
-        .await
-        .expect("failed to get response for BepInEx latest release");
-    let json: Value = response
-        .json()
-        .await
-        .expect("failed to get contents of BepInEx latest release");
-
-    let tag_name_clean = json
-        .get("tag_name")
-        .expect("failed to get tag name for BepInEx release")
-        .as_str()
-        .expect("failed to get tag name as string");
-    let tag_name_clean = &tag_name_clean[1..];
-    let file_name_postfix = format!("_{tag_name_clean}.zip");
-
-    let release_name = format!("{os}_{arch}");
-
-    let (dl_link, dl_size) = json
-        .get("assets")
-        .expect("failed to get assets in BepInEx release")
-        .as_array()
-        .expect("failed to parse assets as array in BepInEx release")
-        .iter()
-        .find_map(|artifact| {
-            let name = artifact
-                .get("name")
-                .expect("failed to get name of BepInEx release")
-                .as_str()
-                .expect("failed to get name of BepInEx release");
-            if name != format!("BepInEx_{release_name}{file_name_postfix}") {
-                return None;
-            }
-
-            let url = artifact
-                .get("browser_download_url")
-                .expect("failed to get BepInEx download url")
-                .as_str()
-                .expect("failed to get BepInEx download url")
-                .to_string();
-
-            let size = artifact
-                .get("size")
-                .expect("failed to get BepInEx download size")
-                .as_u64()
-                .expect("BepInEx download size isn't u64");
-
-            Some((url, size))
-        })
-        .expect("failed to find the BepInEx file in the latest release");
-
-    let mut dl_buff = Vec::with_capacity(dl_size as usize);
-
-    let remove_dl_dir_task = if dl_dir.is_dir() {
-        let dl_dir = dl_dir.to_path_buf();
-        let task = task::spawn(async move {
-            fs::remove_dir_all(dl_dir)
-                .await
-                .expect("failed to remove old BepInEx dir");
-        });
-        Some(task)
-    } else {
-        None
+    release: BepInExRelease,
+    source: Arc<Source>,
+    symlinks: SymlinkPolicy,
+) -> Result<Option<LockedRelease>> {
+    let (version, expected_sha256) = match &release {
+        BepInExRelease::Version(version) => (version.clone(), None),
+        BepInExRelease::Locked(locked) => (
+            BepInExVersion::Tag(locked.tag.to_owned()),
+            Some(locked.sha256.as_str()),
+        ),
     };
 
-    // dl
-    let response = client
-        .get(dl_link)
-        .build()
-        .expect("failed to GET request for BepInEx download");
-
-    let mut bytes = client
-        .execute(response)
-        .await
-        .expect("failed to GET request for BepInEx download")
-        .bytes_stream();
-
-    let pb = pb.add(dl_progress_bar(dl_size));
-    pb.set_message("downloading BepInEx");
-
-    while let Some(chunk) = bytes.next().await {
-        let chunk = chunk.expect("failed to get BepInEx download next chunk");
-        for byte in chunk {
-            dl_buff.push(byte);
+    let (artifact, fetched) = match (source.as_ref(), &release) {
+        // pinned download link is used as is
+        (Source::GithubReleases(releases), BepInExRelease::Locked(locked)) => {
+            let artifact = ResolvedArtifact::from_locked_release(locked);
+            let fetched = fetch_url(&releases.client, &artifact, &pb).await?;
+            (artifact, fetched)
         }
-        pb.set_position(dl_buff.len() as u64);
-    }
-
-    pb.finish_with_message("downloaded BepInEx");
+        _ => {
+            let query = ArtifactQuery::BepInEx {
+                version: &version,
+                backend,
+                os,
+                arch,
+            };
+            let artifact = source
+                .resolve(&query)
+                .await?
+                .into_iter()
+                .next()
+                .with_context(|| format!("failed to find {query}"))?;
+            let fetched = source.fetch(&artifact, &pb).await?;
+            (artifact, fetched)
+        }
+    };
 
-    if let Some(task) = remove_dl_dir_task {
-        task.await.unwrap();
-        fs::create_dir_all(dl_dir)
+    if dl_dir.is_dir() {
+        fs::remove_dir_all(dl_dir)
             .await
-            .expect("failed to create dir for BepInEx download");
+            .with_context(|| format!("failed to remove old BepInEx dir `{}`", dl_dir.display()))?;
     }
 
-    let dl_dir = dl_dir.to_owned();
+    let tag = artifact.tag.as_deref().unwrap_or(&artifact.name);
+    let what = match backend {
+        Backend::Mono => format!("BepInEx {tag}"),
+        Backend::Il2Cpp => format!("BepInEx {tag} IL2CPP"),
+    };
+    let installed = install(fetched, dl_dir, expected_sha256, &what, symlinks).await?;
 
-    task::spawn_blocking(move || {
-        let mut archive = ZipArchive::new(Cursor::new(dl_buff))
-            .expect("failed to load latest BepInEx release as zip archive");
+    let (Some(installed), Location::Url(url)) = (installed, &artifact.location) else {
+        return Ok(None);
+    };
 
-        archive
-            .extract(dl_dir)
-            .expect("failed to extract BepInEx download");
-    })
-    .await
-    .unwrap();
+    Ok(Some(LockedRelease {
+        tag: tag.to_string(),
+        asset: artifact.name.to_owned(),
+        url: url.to_owned(),
+        size: installed.size,
+        sha256: installed.sha256,
+        backend,
+    }))
 }
 
 pub async fn dl_test_games(
     exe_dir: &Path,
     pb: MultiProgress,
-    gh_token: Option<String>,
+    source: Option<Source>,
+    names: Vec<String>,
     replace_games: Vec<ReplaceGame>,
-) -> Result<()> {
-    let Some(gh_token) = gh_token else {
+    locked: Option<Vec<LockedArtifact>>,
+    symlinks: SymlinkPolicy,
+) -> Result<Vec<LockedArtifact>> {
+    let Some(source) = source else {
         // offline mode
 
         let mut copy_tasks: JoinSet<std::result::Result<(), anyhow::Error>> = JoinSet::new();
@@ -277,44 +341,50 @@ pub async fn dl_test_games(
         }
 
         while let Some(res) = copy_tasks.join_next().await {
-            res.unwrap()?;
+            res.context("game copy task failed")??;
         }
 
-        return Ok(());
+        return Ok(Vec::new());
     };
 
-    let artifacts = gh_api::latest_artifacts(
-        "Eddio0141",
-        "UniTASTestClients",
-        &gh_token,
-        "build-on-push.yml",
-        "main",
-        Some(ArtifactFilter::ExcludeNamePattern(
-            Regex::new(&format!("{}.*", env!("CARGO_PKG_NAME"))).unwrap(),
-        )),
-    )
-    .await
-    .context("failed to get latest build of UniTAS test games")?;
-
-    let mut dl_tasks: JoinSet<std::result::Result<(), anyhow::Error>> = JoinSet::new();
+    let artifacts = match (&source, &locked) {
+        (Source::GithubActions(actions), Some(locked)) => locked
+            .iter()
+            .map(|locked| ResolvedArtifact::from_locked_artifact(locked, &actions.api))
+            .collect(),
+        _ => source
+            .resolve(&ArtifactQuery::Games { names: &names })
+            .await
+            .context("failed to get latest build of UniTAS test games")?,
+    };
+
+    let source = Arc::new(source);
+    let mut dl_tasks: JoinSet<std::result::Result<Option<LockedArtifact>, anyhow::Error>> =
+        JoinSet::new();
 
     // now download from links
     for artifact in artifacts {
-        let Artifact { link, dl_len, name } = artifact;
-
         let use_local_file = replace_games.iter().find_map(|replace_game| {
-            if replace_game.name == name {
+            if replace_game.name == artifact.name {
                 Some(replace_game.game_path.to_owned())
             } else {
                 None
             }
         });
 
+        let expected_sha256 = locked.as_ref().and_then(|locked| {
+            locked
+                .iter()
+                .find(|locked| locked.name == artifact.name)
+                .map(|locked| locked.sha256.to_owned())
+        });
+
         let exe_dir = exe_dir.to_path_buf();
         let pb = pb.clone();
-        let gh_token = gh_token.clone();
+        let source = source.clone();
         dl_tasks.spawn(async move {
-            let dl_dir = exe_dir.join(&name);
+            let name = &artifact.name;
+            let dl_dir = exe_dir.join(name);
 
             if let Some(use_local_folder) = use_local_file {
                 fs_utils::copy_dir_all(&use_local_folder, &dl_dir)
@@ -327,83 +397,133 @@ pub async fn dl_test_games(
                         )
                     })?;
 
-                return Ok(());
+                return Ok(None);
             }
 
-            let dl_fail_err = |name, link| {
-                format!("failed to get response for downloading game `{name}` with link `{link}`")
-            };
-
-            let response = gh_api::gh_api_client(&link, &gh_token)
-                .await
-                .with_context(|| dl_fail_err(&name, &link))?
-                .send()
-                .await
-                .with_context(|| dl_fail_err(&name, &link))?;
+            let fetched = source.fetch(&artifact, &pb).await?;
+            let installed = install(
+                fetched,
+                &dl_dir,
+                expected_sha256.as_deref(),
+                &format!("game `{name}`"),
+                symlinks,
+            )
+            .await?;
+
+            Ok(installed.and_then(|installed| lock_artifact(&artifact, &installed)))
+        });
+    }
 
-            let mut dl_buff = Vec::with_capacity(dl_len as usize);
+    let mut locked = Vec::new();
+    while let Some(res) = dl_tasks.join_next().await {
+        if let Some(artifact) = res.context("game download task failed")?? {
+            locked.push(artifact);
+        }
+    }
 
-            let mut bytes = response.bytes_stream();
+    // keep lockfile diffs stable
+    locked.sort_by(|a, b| a.name.cmp(&b.name));
 
-            let pb = pb.add(dl_progress_bar(dl_len));
-            pb.set_message(format!("downloading game `{name}`"));
+    Ok(locked)
+}
 
-            while let Some(chunk) = bytes.next().await {
-                let chunk = chunk.expect("failed to download unity game contents");
+/// Downloaded zip which got installed
+struct Installed {
+    size: u64,
+    sha256: String,
+}
 
-                for byte in chunk {
-                    dl_buff.push(byte);
-                }
-                pb.set_position(dl_buff.len() as u64);
+/// Verifies and extracts the fetched artifact into `dir`
+///
+/// Returns `None` if the artifact was an already extracted directory
+async fn install(
+    fetched: Fetched,
+    dir: &Path,
+    expected_sha256: Option<&str>,
+    what: &str,
+    symlinks: SymlinkPolicy,
+) -> Result<Option<Installed>> {
+    match fetched {
+        Fetched::Zip(bytes) => {
+            if let Some(expected) = expected_sha256 {
+                verify_sha256(&bytes, expected, what)?;
             }
 
-            pb.finish_with_message(format!("downloaded game `{name}`"));
+            let installed = Installed {
+                size: bytes.len() as u64,
+                sha256: sha256_hex(&bytes),
+            };
 
-            fs::create_dir_all(&dl_dir)
+            let dir = dir.to_path_buf();
+            task::spawn_blocking(move || extract_zip(bytes, &dir, symlinks))
                 .await
-                .expect("failed to create directory for unity game");
-
-            let mut archive = ZipArchive::new(Cursor::new(dl_buff))
-                .expect("failed to load unity game as zip archive");
-
-            {
-                let dl_dir = dl_dir.clone();
-                task::spawn_blocking(move || {
-                    archive
-                        .extract(&dl_dir)
-                        .expect("failed to extract unity game");
-                })
+                .with_context(|| format!("extracting {what} failed"))?
+                .with_context(|| format!("failed to extract {what}"))?;
+
+            Ok(Some(installed))
+        }
+        Fetched::Dir(src) => {
+            if expected_sha256.is_some() {
+                warn!("{what} is an already extracted directory, sha256 from the lockfile can't be verified");
             }
-            .await
-            .unwrap();
 
-            // chmod game binary
-            #[cfg(target_family = "unix")]
-            {
-                let game_bin = dl_dir.join(UNIX_UNITY_EXE_NAME);
+            fs_utils::copy_dir_all(&src, dir).await.with_context(|| {
+                format!(
+                    "failed to copy {what} from `{}` to `{}`",
+                    src.display(),
+                    dir.display()
+                )
+            })?;
 
-                // set perms for execution
-                let mut perms = game_bin
-                    .metadata()
-                    .expect("failed to get game file metadata")
-                    .permissions();
+            Ok(None)
+        }
+    }
+}
 
-                perms.set_mode(0o744);
+/// Only github actions artifacts can be pinned in the lockfile
+fn lock_artifact(artifact: &ResolvedArtifact, installed: &Installed) -> Option<LockedArtifact> {
+    let origin = artifact.origin.as_ref()?;
+
+    Some(LockedArtifact {
+        name: artifact.name.to_owned(),
+        owner: origin.owner.to_owned(),
+        repo: origin.repo.to_owned(),
+        workflow: origin.workflow.to_owned(),
+        run_id: origin.run_id,
+        artifact_id: origin.artifact_id,
+        size: installed.size,
+        sha256: installed.sha256.to_owned(),
+    })
+}
 
-                fs::set_permissions(game_bin, perms)
-                    .await
-                    .expect("failed to set execute permissions for game");
+async fn dl_with_progress(
+    response: Response,
+    dl_len: u64,
+    pb: &MultiProgress,
+    what: &str,
+) -> Result<Vec<u8>> {
+    let mut dl_buff = Vec::with_capacity(dl_len as usize);
+    let mut bytes = response.bytes_stream();
+
+    let pb = pb.add(dl_progress_bar(dl_len));
+    pb.set_message(format!("downloading {what}"));
+
+    while let Some(chunk) = bytes.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                pb.abandon_with_message(format!("failed to download {what}"));
+                return Err(err).with_context(|| format!("failed to download {what}"));
             }
+        };
+        dl_buff.extend_from_slice(&chunk);
 
-            Ok(())
-        });
+        pb.set_position(dl_buff.len() as u64);
     }
 
-    while let Some(res) = dl_tasks.join_next().await {
-        res.unwrap()?;
-    }
+    pb.finish_with_message(format!("downloaded {what}"));
 
-    Ok(())
+    Ok(dl_buff)
 }
 
 fn dl_progress_bar(dl_size: u64) -> ProgressBar {
@@ -421,3 +541,312 @@ fn dl_progress_bar(dl_size: u64) -> ProgressBar {
 
     pb
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::WorkflowBuild;
+    use crate::download::test_server::{
+        fixture_zip, hidden_progress, FakeGithub, FixtureArtifact, FixtureRun, OWNER, REPO, TOKEN,
+        WORKFLOW,
+    };
+
+    fn actions_source(github: &FakeGithub) -> Source {
+        Source::GithubActions(GithubActions {
+            api: github.gh_api(),
+            workflow: GithubWorkflow {
+                owner: OWNER.to_string(),
+                repo: REPO.to_string(),
+                workflow: WORKFLOW.to_string(),
+                build: WorkflowBuild::Branch("main".to_string()),
+            },
+            token: TOKEN.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn downloads_test_games_from_latest_run() {
+        let github = FakeGithub::start().await;
+        github
+            .runs(&[
+                FixtureRun::failure(2, "main"),
+                FixtureRun::success(1, "main"),
+            ])
+            .await;
+        let game = fixture_zip(&[("build.x86_64", b"game"), ("data/level", b"level")]);
+        github
+            .artifacts(
+                1,
+                &[
+                    FixtureArtifact::new(10, "unity_latest", game.clone()),
+                    FixtureArtifact::new(11, "test-runner-linux", fixture_zip(&[])),
+                ],
+            )
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let locked = dl_test_games(
+            dir.path(),
+            hidden_progress(),
+            Some(actions_source(&github)),
+            vec!["unity_latest".to_string()],
+            Vec::new(),
+            None,
+            SymlinkPolicy::Reject,
+        )
+        .await
+        .unwrap();
+
+        let game_dir = dir.path().join("unity_latest");
+        assert_eq!(
+            std::fs::read(game_dir.join("data/level")).unwrap(),
+            b"level"
+        );
+        assert!(!dir.path().join("test-runner-linux").exists());
+
+        assert_eq!(locked.len(), 1);
+        assert_eq!(locked[0].name, "unity_latest");
+        assert_eq!(locked[0].run_id, 1);
+        assert_eq!(locked[0].artifact_id, 10);
+        assert_eq!(locked[0].sha256, sha256_hex(&game));
+    }
+
+    #[tokio::test]
+    async fn locked_test_games_are_verified() {
+        let github = FakeGithub::start().await;
+        github
+            .artifacts(
+                1,
+                &[FixtureArtifact::new(
+                    10,
+                    "unity_latest",
+                    fixture_zip(&[("build.x86_64", b"game")]),
+                )],
+            )
+            .await;
+
+        let locked = LockedArtifact {
+            name: "unity_latest".to_string(),
+            owner: OWNER.to_string(),
+            repo: REPO.to_string(),
+            workflow: WORKFLOW.to_string(),
+            run_id: 1,
+            artifact_id: 10,
+            size: 0,
+            sha256: sha256_hex(b"something else"),
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let Err(err) = dl_test_games(
+            dir.path(),
+            hidden_progress(),
+            Some(actions_source(&github)),
+            vec!["unity_latest".to_string()],
+            Vec::new(),
+            Some(vec![locked]),
+            SymlinkPolicy::Reject,
+        )
+        .await
+        else {
+            panic!("game with a different sha256 than locked shouldn't be installed");
+        };
+
+        assert!(err.to_string().contains("sha256 mismatch"));
+    }
+
+    #[tokio::test]
+    async fn install_verifies_sha256() {
+        let zip = fixture_zip(&[("UniTAS.dll", b"unitas")]);
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("UniTAS");
+
+        let Err(err) = install(
+            Fetched::Zip(zip.clone()),
+            &dir,
+            Some(&sha256_hex(b"something else")),
+            "UniTAS",
+            SymlinkPolicy::Reject,
+        )
+        .await
+        else {
+            panic!("zip with a different sha256 than expected shouldn't be installed");
+        };
+        assert!(err.to_string().contains("sha256 mismatch"), "{err:#}");
+        assert!(!dir.exists());
+
+        let installed = install(
+            Fetched::Zip(zip.clone()),
+            &dir,
+            Some(&sha256_hex(&zip)),
+            "UniTAS",
+            SymlinkPolicy::Reject,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(installed.sha256, sha256_hex(&zip));
+        assert_eq!(fs::read(dir.join("UniTAS.dll")).await.unwrap(), b"unitas");
+    }
+
+    #[tokio::test]
+    async fn downloads_unitas_artifact() {
+        let github = FakeGithub::start().await;
+        github.runs(&[FixtureRun::success(1, "main")]).await;
+        github
+            .artifacts(
+                1,
+                &[
+                    FixtureArtifact::new(10, "UniTAS", fixture_zip(&[("UniTAS.dll", b"dll")])),
+                    FixtureArtifact::new(11, "unity_latest", fixture_zip(&[])),
+                ],
+            )
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let unitas_dir = dir.path().join("UniTAS");
+        let locked = dl_unitas(
+            &unitas_dir,
+            true,
+            Some(actions_source(&github)),
+            hidden_progress(),
+            None,
+            SymlinkPolicy::Reject,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read(unitas_dir.join("UniTAS.dll")).unwrap(),
+            b"dll"
+        );
+        assert_eq!(locked.artifact_id, 10);
+    }
+
+    #[tokio::test]
+    async fn downloads_bepinex_release_asset() {
+        let github = FakeGithub::start().await;
+        let bepinex = fixture_zip(&[("run_bepinex.sh", b"executable_name=\"\"")]);
+        github
+            .release(
+                "v5.4.23.2",
+                true,
+                &[
+                    ("BepInEx_win_x64_5.4.23.2.zip", fixture_zip(&[])),
+                    ("BepInEx_linux_x64_5.4.23.2.zip", bepinex.clone()),
+                ],
+            )
+            .await;
+
+        let source = Arc::new(Source::GithubReleases(GithubReleases {
+            client: Client::new(),
+            releases_url: github.releases_url(),
+        }));
+        let dir = tempfile::tempdir().unwrap();
+        let bepinex_dir = dir.path().join("BepInEx");
+        let locked = dl_bepinex(
+            &bepinex_dir,
+            &Os::Linux,
+            &Arch::X64,
+            Backend::Mono,
+            hidden_progress(),
+            BepInExRelease::Version(BepInExVersion::Latest),
+            source,
+            SymlinkPolicy::Reject,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert!(bepinex_dir.join("run_bepinex.sh").is_file());
+        assert_eq!(locked.tag, "v5.4.23.2");
+        assert_eq!(locked.asset, "BepInEx_linux_x64_5.4.23.2.zip");
+        assert_eq!(locked.sha256, sha256_hex(&bepinex));
+    }
+
+    #[tokio::test]
+    async fn latest_il2cpp_bepinex_is_found_in_pre_releases() {
+        let github = FakeGithub::start().await;
+        let bepinex = fixture_zip(&[("run_bepinex.sh", b"executable_name=\"\"")]);
+        github
+            .releases(&[
+                (
+                    "v5.4.23.2",
+                    &[("BepInEx_linux_x64_5.4.23.2.zip", fixture_zip(&[]))],
+                ),
+                (
+                    "v6.0.0-pre.2",
+                    &[
+                        (
+                            "BepInEx-Unity.Mono-linux-x64-6.0.0-pre.2.zip",
+                            fixture_zip(&[]),
+                        ),
+                        (
+                            "BepInEx-Unity.IL2CPP-linux-x64-6.0.0-pre.2.zip",
+                            bepinex.clone(),
+                        ),
+                    ],
+                ),
+            ])
+            .await;
+
+        let source = Arc::new(Source::GithubReleases(GithubReleases {
+            client: Client::new(),
+            releases_url: github.releases_url(),
+        }));
+        let dir = tempfile::tempdir().unwrap();
+        let bepinex_dir = dir.path().join("BepInEx-IL2CPP");
+        let locked = dl_bepinex(
+            &bepinex_dir,
+            &Os::Linux,
+            &Arch::X64,
+            Backend::Il2Cpp,
+            hidden_progress(),
+            BepInExRelease::Version(BepInExVersion::Latest),
+            source,
+            SymlinkPolicy::Reject,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert!(bepinex_dir.join("run_bepinex.sh").is_file());
+        assert_eq!(locked.tag, "v6.0.0-pre.2");
+        assert_eq!(
+            locked.asset,
+            "BepInEx-Unity.IL2CPP-linux-x64-6.0.0-pre.2.zip"
+        );
+        assert_eq!(locked.backend, Backend::Il2Cpp);
+    }
+
+    #[tokio::test]
+    async fn dropped_connection_is_an_error() {
+        use std::io::{Read, Write};
+
+        // promises more bytes than it sends before closing the connection
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\nonly a bit");
+        });
+
+        let artifact = ResolvedArtifact {
+            name: "UniTAS.zip".to_string(),
+            location: source::Location::Url(format!("http://{addr}/UniTAS.zip")),
+            size: 0,
+            tag: None,
+            origin: None,
+        };
+        let Err(err) = source::fetch_url(&Client::new(), &artifact, &hidden_progress()).await
+        else {
+            panic!("a truncated download should fail");
+        };
+
+        assert!(
+            format!("{err:#}").contains("failed to download UniTAS.zip"),
+            "{err:#}"
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::lockfile::sha256_hex;
+
+/// What to do with symlink entries in downloaded archives
+#[derive(Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    /// Fail extraction if the archive contains any symlink
+    Reject,
+    /// Don't extract symlinks
+    Skip,
+    /// Only extract symlinks pointing to somewhere inside the extract directory
+    Within,
+}
+
+/// Files written while extracting an archive
+#[derive(Serialize, Default)]
+pub struct ExtractManifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+/// Extracts zip into `dest`, refusing any entry which would be written outside of `dest`
+///
+/// The manifest of extracted files is written next to `dest` as `<dest>.manifest.toml`
+pub fn extract_zip(
+    bytes: Vec<u8>,
+    dest: &Path,
+    symlinks: SymlinkPolicy,
+) -> Result<ExtractManifest> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).context("failed to load zip archive")?;
+
+    fs::create_dir_all(dest)
+        .with_context(|| format!("failed to create directory `{}`", dest.display()))?;
+
+    let mut manifest = ExtractManifest::default();
+    #[cfg(target_family = "unix")]
+    let mut file_modes = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive
+            .by_index(i)
+            .with_context(|| format!("failed to read zip entry at index {i}"))?;
+
+        let rel_path = entry_path(file.name())?;
+        let out_path = dest.join(&rel_path);
+        // links extracted earlier could otherwise redirect this entry anywhere
+        if let Some(link) = symlinked_parent(dest, &rel_path) {
+            bail!(
+                "zip entry `{}` would be written through the symlink `{}`",
+                file.name(),
+                manifest_path(&link)
+            );
+        }
+
+        if file.is_dir() {
+            fs::create_dir_all(&out_path)
+                .with_context(|| format!("failed to create directory `{}`", out_path.display()))?;
+            continue;
+        }
+
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directory `{}`", parent.display()))?;
+        }
+
+        if file.is_symlink() {
+            let mut target = String::new();
+            file.read_to_string(&mut target)
+                .with_context(|| format!("failed to read symlink entry `{}`", file.name()))?;
+
+            match symlinks {
+                SymlinkPolicy::Reject => {
+                    bail!(
+                        "zip entry `{}` is a symlink to `{target}`, which isn't allowed",
+                        file.name()
+                    );
+                }
+                SymlinkPolicy::Skip => {
+                    warn!("skipping symlink zip entry `{}` -> `{target}`", file.name());
+                    continue;
+                }
+                SymlinkPolicy::Within => {
+                    if !link_within(dest, &rel_path, &target) {
+                        bail!(
+                            "zip entry `{}` is a symlink to `{target}`, which points outside of the extract directory",
+                            file.name()
+                        );
+                    }
+                }
+            }
+
+            make_symlink(&target, &out_path)?;
+            manifest.files.push(ManifestEntry {
+                path: manifest_path(&rel_path),
+                size: 0,
+                sha256: None,
+                link: Some(target),
+            });
+            continue;
+        }
+
+        let mut contents = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut contents)
+            .with_context(|| format!("failed to read zip entry `{}`", file.name()))?;
+
+        // never write through an existing symlink
+        if out_path.is_symlink() {
+            fs::remove_file(&out_path)
+                .with_context(|| format!("failed to remove symlink `{}`", out_path.display()))?;
+        }
+        fs::write(&out_path, &contents)
+            .with_context(|| format!("failed to write file `{}`", out_path.display()))?;
+
+        #[cfg(target_family = "unix")]
+        if let Some(mode) = file.unix_mode() {
+            file_modes.push((out_path.clone(), mode));
+        }
+
+        manifest.files.push(ManifestEntry {
+            path: manifest_path(&rel_path),
+            size: contents.len() as u64,
+            sha256: Some(sha256_hex(&contents)),
+            link: None,
+        });
+    }
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        for (path, mode) in file_modes {
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode))
+                .with_context(|| format!("failed to set permissions of `{}`", path.display()))?;
+        }
+    }
+
+    write_manifest(&manifest, dest)?;
+
+    Ok(manifest)
+}
+
+/// Validates a zip entry name into a path relative to the extract directory
+fn entry_path(name: &str) -> Result<PathBuf> {
+    // zip files made on windows can use either separator
+    let name = name.replace('\\', "/");
+
+    if name.starts_with('/') {
+        bail!("zip entry `{name}` has an absolute path");
+    }
+
+    let mut path = PathBuf::new();
+    for component in Path::new(&name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => (),
+            Component::ParentDir => bail!("zip entry `{name}` contains a `..` component"),
+            Component::RootDir | Component::Prefix(_) => {
+                bail!("zip entry `{name}` has an absolute path")
+            }
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        bail!("zip entry `{name}` has an empty path");
+    }
+
+    Ok(path)
+}
+
+/// Checks if symlink at `rel_path` pointing to `target` stays inside the extract directory,
+/// following the links already extracted into `dest`
+fn link_within(dest: &Path, rel_path: &Path, target: &str) -> bool {
+    let parent = rel_path.parent().unwrap_or(Path::new(""));
+    resolve_within(dest, parent, target, 0).is_some()
+}
+
+/// Links followed while resolving a link target before giving up, like the limit of the OS
+const MAX_LINK_DEPTH: usize = 40;
+
+/// Resolves `target` from the directory `base` relative to `dest`, following extracted links
+///
+/// Unset if the target leaves `dest` at any point
+fn resolve_within(dest: &Path, base: &Path, target: &str, depth: usize) -> Option<PathBuf> {
+    if depth > MAX_LINK_DEPTH {
+        return None;
+    }
+
+    let target = target.replace('\\', "/");
+    let target = Path::new(&target);
+    if target.is_absolute() {
+        return None;
+    }
+
+    let mut resolved = base.to_path_buf();
+    for component in target.components() {
+        match component {
+            Component::Normal(part) => {
+                resolved.push(part);
+                let path = dest.join(&resolved);
+                if path.is_symlink() {
+                    let link_target = fs::read_link(&path).ok()?;
+                    let link_base = resolved.parent().unwrap_or(Path::new("")).to_path_buf();
+                    resolved = resolve_within(
+                        dest,
+                        &link_base,
+                        &link_target.to_string_lossy(),
+                        depth + 1,
+                    )?;
+                }
+            }
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(resolved)
+}
+
+/// First parent directory of `rel_path` inside `dest` which is a symlink
+fn symlinked_parent(dest: &Path, rel_path: &Path) -> Option<PathBuf> {
+    rel_path
+        .ancestors()
+        .skip(1)
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .find(|parent| dest.join(parent).is_symlink())
+        .map(Path::to_path_buf)
+}
+
+#[cfg(target_family = "unix")]
+fn make_symlink(target: &str, path: &Path) -> Result<()> {
+    if path.is_symlink() || path.is_file() {
+        fs::remove_file(path)
+            .with_context(|| format!("failed to remove existing file `{}`", path.display()))?;
+    }
+
+    std::os::unix::fs::symlink(target, path)
+        .with_context(|| format!("failed to create symlink `{}`", path.display()))
+}
+
+#[cfg(not(target_family = "unix"))]
+fn make_symlink(target: &str, path: &Path) -> Result<()> {
+    warn!(
+        "symlinks aren't supported on this platform, skipping `{}` -> `{target}`",
+        path.display()
+    );
+    Ok(())
+}
+
+fn manifest_path(rel_path: &Path) -> String {
+    rel_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn write_manifest(manifest: &ExtractManifest, dest: &Path) -> Result<()> {
+    let mut manifest_file = dest.as_os_str().to_owned();
+    manifest_file.push(".manifest.toml");
+    let manifest_file = PathBuf::from(manifest_file);
+
+    let contents = toml::to_string_pretty(manifest).context("failed to serialize manifest")?;
+    fs::write(&manifest_file, contents).with_context(|| {
+        format!(
+            "failed to write extract manifest to `{}`",
+            manifest_file.display()
+        )
+    })?;
+
+    debug!(
+        "extracted {} files into `{}`, manifest at `{}`",
+        manifest.files.len(),
+        dest.display(),
+        manifest_file.display()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_family = "unix")]
+    use std::io::Write;
+
+    #[cfg(target_family = "unix")]
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+    use crate::download::test_server::fixture_zip;
+
+    /// Extracting the entry named from where `evil` outside of the extract directory is, after a
+    /// valid file, fails without writing `evil`
+    fn assert_refused(name: impl Fn(&Path) -> String) {
+        let root = tempfile::tempdir().unwrap();
+        let dest = root.path().join("extract");
+        let evil = root.path().join("evil");
+        let name = name(&evil);
+
+        let Err(err) = extract_zip(
+            fixture_zip(&[("ok.txt", b"ok"), (&name, b"evil")]),
+            &dest,
+            SymlinkPolicy::Reject,
+        ) else {
+            panic!("extracting `{name}` should fail");
+        };
+
+        assert!(err.to_string().contains(&name), "{err:#}");
+        assert!(!evil.exists(), "`{}` was written", evil.display());
+    }
+
+    #[test]
+    fn entries_outside_dest_are_refused() {
+        assert_refused(|_| "../evil".to_string());
+        assert_refused(|_| "a/../../evil".to_string());
+        assert_refused(|evil| evil.to_string_lossy().to_string());
+        #[cfg(target_family = "windows")]
+        {
+            assert_refused(|evil| evil.to_string_lossy().replace('\\', "/"));
+            assert_refused(|_| "C:evil".to_string());
+        }
+    }
+
+    #[test]
+    fn entry_paths() {
+        assert_eq!(
+            entry_path("./BepInEx\\core/file.dll").unwrap(),
+            Path::new("BepInEx").join("core").join("file.dll")
+        );
+        assert!(entry_path("/abs").is_err());
+        assert!(entry_path("\\abs").is_err());
+        assert!(entry_path("a/../b").is_err());
+        assert!(entry_path("./").is_err());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn chained_links_cant_escape() {
+        let root = tempfile::tempdir().unwrap();
+        let dest = root.path().join("extract");
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        zip.add_directory("a", options).unwrap();
+        zip.add_symlink("a/b", "..", options).unwrap();
+        zip.add_symlink("c", "a/b/..", options).unwrap();
+        zip.start_file("c/x", options).unwrap();
+        zip.write_all(b"escaped").unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let Err(err) = extract_zip(bytes, &dest, SymlinkPolicy::Within) else {
+            panic!("extraction should fail");
+        };
+
+        assert!(err.to_string().contains("zip entry `c`"), "{err:#}");
+        assert!(!root.path().join("x").exists());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn files_arent_written_through_links() {
+        let root = tempfile::tempdir().unwrap();
+        let dest = root.path().join("extract");
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        zip.add_directory("a", options).unwrap();
+        zip.add_symlink("link", "a", options).unwrap();
+        zip.start_file("link/x", options).unwrap();
+        zip.write_all(b"through link").unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let Err(err) = extract_zip(bytes, &dest, SymlinkPolicy::Within) else {
+            panic!("extraction should fail");
+        };
+
+        assert!(
+            err.to_string().contains("through the symlink `link`"),
+            "{err:#}"
+        );
+        assert!(!dest.join("a").join("x").exists());
+        assert!(resolve_within(&dest, Path::new(""), "link", 0).is_some());
+    }
+}
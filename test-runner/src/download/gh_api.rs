@@ -1,13 +1,149 @@
-use std::result;
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{bail, Context, Result};
+use log::{debug, warn};
 use regex::Regex;
-use reqwest::{header::ACCEPT, RequestBuilder};
-use serde_json::Value;
+use reqwest::{
+    header::{HeaderMap, ACCEPT},
+    Client, Response, StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use thiserror::Error;
 
-const GITHUB_API_LINK: &str = "https://api.github.com";
+/// Default base URL of the github api
+pub const GITHUB_API_LINK: &str = "https://api.github.com";
+
+/// Max page size github allows
+const PER_PAGE: &str = "100";
+
+/// Longest time to wait for a rate limit reset before giving up
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(5 * 60);
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Wait for secondary rate limits without a reset time, as github docs recommend
+const SECONDARY_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Github api server and the client used to talk to it
+#[derive(Clone)]
+pub struct GhApi {
+    pub client: Client,
+    /// Base URL of the github api, like [`GITHUB_API_LINK`]
+    pub base_url: String,
+}
+
+#[derive(Error, Debug)]
+pub enum GhApiError {
+    #[error("invalid github api url `{url}`")]
+    InvalidUrl {
+        url: String,
+        #[source]
+        source: <Url as FromStr>::Err,
+    },
+    #[error("failed to send request to `{url}`")]
+    Request {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("github api responded with `{status}` for `{url}`: {message}")]
+    Status {
+        url: String,
+        status: StatusCode,
+        message: String,
+    },
+    #[error("github api rate limit exceeded for `{url}`, limit resets in {} seconds", wait.as_secs())]
+    RateLimited { url: String, wait: Duration },
+    #[error("failed to parse github api response from `{url}`")]
+    Decode {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("couldn't find successful run of workflow `{workflow}` for {run}")]
+    NoSuccessfulRun { workflow: String, run: String },
+    #[error("github actions run by id {0} didn't complete successfully")]
+    RunNotSuccessful(u64),
+}
+
+type Result<T> = std::result::Result<T, GhApiError>;
+
+#[derive(Deserialize)]
+pub struct Workflow {
+    pub id: u64,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct WorkflowRuns {
+    total_count: u64,
+    workflow_runs: Vec<WorkflowRun>,
+}
+
+#[derive(Deserialize)]
+pub struct WorkflowRun {
+    pub id: u64,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub status: Option<String>,
+    pub conclusion: Option<String>,
+}
+
+impl WorkflowRun {
+    fn succeeded(&self) -> bool {
+        self.status.as_deref() == Some("completed") && self.conclusion.as_deref() == Some("success")
+    }
+}
+
+#[derive(Deserialize)]
+struct WorkflowArtifacts {
+    total_count: u64,
+    artifacts: Vec<WorkflowArtifact>,
+}
+
+#[derive(Deserialize)]
+struct WorkflowArtifact {
+    id: u64,
+    name: String,
+    size_in_bytes: u64,
+    archive_download_url: String,
+    #[serde(default)]
+    expired: bool,
+}
+
+#[derive(Deserialize)]
+struct PullRequest {
+    head: PullRequestHead,
+}
+
+#[derive(Deserialize)]
+struct PullRequestHead {
+    sha: String,
+}
+
+#[derive(Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub size: u64,
+    pub browser_download_url: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    message: String,
+}
 
 pub struct Artifact {
+    pub id: u64,
+    pub run_id: u64,
     pub link: String,
     pub dl_len: u64,
     pub name: String,
@@ -18,172 +154,626 @@ pub enum ArtifactFilter<'a> {
     ExcludeNamePattern(Regex),
 }
 
+impl ArtifactFilter<'_> {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            ArtifactFilter::TargetName(n) => name == *n,
+            ArtifactFilter::ExcludeNamePattern(pattern) => !pattern.is_match(name),
+        }
+    }
+}
+
+/// Which workflow run to take artifacts from
+pub enum RunSelector<'a> {
+    /// Latest successful run on the branch
+    Branch(&'a str),
+    /// Latest successful run for the commit
+    HeadSha(&'a str),
+    /// Exact run by its id
+    Id(u64),
+}
+
+impl Display for RunSelector<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunSelector::Branch(branch) => write!(f, "branch `{branch}`"),
+            RunSelector::HeadSha(sha) => write!(f, "commit `{sha}`"),
+            RunSelector::Id(id) => write!(f, "run id `{id}`"),
+        }
+    }
+}
+
+impl RunSelector<'_> {
+    fn matches(&self, run: &WorkflowRun) -> bool {
+        match self {
+            RunSelector::Branch(branch) => run.head_branch.as_deref() == Some(*branch),
+            RunSelector::HeadSha(sha) => run.head_sha.starts_with(sha),
+            RunSelector::Id(id) => run.id == *id,
+        }
+    }
+
+    /// Server side filters for listing workflow runs
+    fn query(&self) -> Vec<(&str, &str)> {
+        match self {
+            RunSelector::Branch(branch) => vec![("branch", branch)],
+            // only full SHA can be filtered by github
+            RunSelector::HeadSha(sha) if sha.len() == 40 => vec![("head_sha", sha)],
+            RunSelector::HeadSha(_) | RunSelector::Id(_) => Vec::new(),
+        }
+    }
+}
+
 pub async fn latest_artifacts(
+    api: &GhApi,
     owner: &str,
     repo: &str,
     token: &str,
     workflow_name: &str,
-    branch: &str,
+    run: RunSelector<'_>,
     filter: Option<ArtifactFilter<'_>>,
 ) -> Result<Vec<Artifact>> {
-    let workflow_id = get(
+    let run_id = match run {
+        RunSelector::Id(id) => {
+            let run: WorkflowRun = get(
+                api,
+                owner,
+                repo,
+                Some(token),
+                &format!("actions/runs/{id}"),
+                &[],
+            )
+            .await?;
+
+            if !run.succeeded() {
+                return Err(GhApiError::RunNotSuccessful(id));
+            }
+
+            id
+        }
+        RunSelector::Branch(_) | RunSelector::HeadSha(_) => {
+            latest_successful_run(api, owner, repo, token, workflow_name, &run).await?
+        }
+    };
+
+    let mut artifacts = Vec::new();
+    let mut page = 1u64;
+    loop {
+        let page_str = page.to_string();
+        let response: WorkflowArtifacts = get(
+            api,
+            owner,
+            repo,
+            Some(token),
+            &format!("actions/runs/{run_id}/artifacts"),
+            &[("per_page", PER_PAGE), ("page", &page_str)],
+        )
+        .await?;
+
+        let count = response.artifacts.len();
+        artifacts.extend(response.artifacts);
+
+        if count == 0 || artifacts.len() as u64 >= response.total_count {
+            break;
+        }
+        page += 1;
+    }
+
+    let artifacts = artifacts
+        .into_iter()
+        .filter(|a| {
+            if a.expired {
+                warn!("skipping expired artifact `{}` of run {run_id}", a.name);
+                return false;
+            }
+            filter.as_ref().is_none_or(|filter| filter.matches(&a.name))
+        })
+        .map(|a| Artifact {
+            id: a.id,
+            run_id,
+            link: a.archive_download_url,
+            dl_len: a.size_in_bytes,
+            name: a.name,
+        })
+        .collect();
+
+    Ok(artifacts)
+}
+
+async fn latest_successful_run(
+    api: &GhApi,
+    owner: &str,
+    repo: &str,
+    token: &str,
+    workflow_name: &str,
+    run: &RunSelector<'_>,
+) -> Result<u64> {
+    let workflow: Workflow = get(
+        api,
         owner,
         repo,
-        token,
+        Some(token),
         &format!("actions/workflows/{workflow_name}"),
+        &[],
     )
-    .await
-    .with_context(|| format!("failed to get workflow by name `{workflow_name}`"))?;
-    let workflow_id = workflow_id.get("id").unwrap().as_u64().unwrap();
+    .await?;
+    debug!(
+        "found workflow `{}` by id {} from `{workflow_name}`",
+        workflow.name, workflow.id
+    );
 
-    let get_latest_action_fail_msg =
-        || format!("failed to get latest github actions run by id {workflow_id}");
+    let mut seen = 0u64;
+    let mut page = 1u64;
+    loop {
+        let page_str = page.to_string();
+        let mut query = run.query();
+        query.extend([
+            ("status", "success"),
+            ("per_page", PER_PAGE),
+            ("page", &page_str),
+        ]);
 
-    let get_run = |page: u64| async move {
-        get(
+        let runs: WorkflowRuns = get(
+            api,
             owner,
             repo,
-            token,
-            &format!("actions/workflows/{workflow_id}/runs?per_page=1&page={page}"),
+            Some(token),
+            &format!("actions/workflows/{}/runs", workflow.id),
+            &query,
         )
-        .await
-        .with_context(get_latest_action_fail_msg)
-    };
-
-    let latest_run = get_run(1).await?;
+        .await?;
 
-    // check if use run, return Some(id) if its valid
-    let use_run = |workflow: &Value| {
-        if workflow.get("status").unwrap().as_str().unwrap() == "completed"
-            && workflow.get("head_branch").unwrap().as_str().unwrap() == branch
-            && workflow.get("conclusion").unwrap().as_str().unwrap() == "success"
+        // runs are sorted by newest first
+        if let Some(found) = runs
+            .workflow_runs
+            .iter()
+            .find(|r| r.succeeded() && run.matches(r))
         {
-            Some(workflow.get("id").unwrap().as_u64().unwrap())
-        } else {
-            None
+            return Ok(found.id);
         }
-    };
-    let run_count = latest_run.get("total_count").unwrap().as_u64().unwrap();
-    let mut latest_run_id = use_run(
-        latest_run
-            .get("workflow_runs")
-            .unwrap()
-            .get(0)
-            .with_context(get_latest_action_fail_msg)?,
-    );
-
-    if latest_run_id.is_none() {
-        for i in 1..run_count {
-            let run = get_run(i + 1).await?;
-            latest_run_id = use_run(
-                run.get("workflow_runs")
-                    .unwrap()
-                    .get(0)
-                    .with_context(get_latest_action_fail_msg)?,
-            );
 
-            if latest_run_id.is_some() {
-                break;
-            }
+        seen += runs.workflow_runs.len() as u64;
+        if runs.workflow_runs.is_empty() || seen >= runs.total_count {
+            break;
         }
+        page += 1;
     }
 
-    let Some(latest_run_id) = latest_run_id else {
-        bail!("couldn't find workflow by name `{workflow_name}` and branch `{branch}`");
-    };
+    Err(GhApiError::NoSuccessfulRun {
+        workflow: workflow_name.to_string(),
+        run: run.to_string(),
+    })
+}
 
-    let artifacts = get(
+/// Head commit of a pull request, runs triggered by the pull request are built from this commit
+pub async fn pull_request_head_sha(
+    api: &GhApi,
+    owner: &str,
+    repo: &str,
+    token: &str,
+    number: u64,
+) -> Result<String> {
+    let pull: PullRequest = get(
+        api,
         owner,
         repo,
-        token,
-        &format!("actions/runs/{latest_run_id}/artifacts"),
+        Some(token),
+        &format!("pulls/{number}"),
+        &[],
     )
-    .await
-    .with_context(get_latest_action_fail_msg)?;
-
-    let urls = artifacts
-        .get("artifacts")
-        .unwrap()
-        .as_array()
-        .unwrap()
-        .iter();
-
-    let urls = if let Some(filter) = filter {
-        urls.filter_map(|a| {
-            let artifact_name = artifact_name(a);
-
-            let matches = match &filter {
-                ArtifactFilter::TargetName(n) => artifact_name == *n,
-                ArtifactFilter::ExcludeNamePattern(pattern) => !pattern.is_match(artifact_name),
-            };
-
-            if matches {
-                Some(Artifact {
-                    name: artifact_name.to_owned(),
-                    link: artifact_dl_link(a).to_owned(),
-                    dl_len: artifact_size(a),
-                })
-            } else {
-                None
-            }
-        })
-        .collect()
-    } else {
-        urls.map(|a| Artifact {
-            name: artifact_name(a).to_owned(),
-            link: artifact_dl_link(a).to_owned(),
-            dl_len: artifact_size(a),
-        })
-        .collect()
+    .await?;
+    Ok(pull.head.sha)
+}
+
+/// Release by tag, or the latest release if tag isn't given
+///
+/// `releases_url` is the releases endpoint of a repository, like `https://api.github.com/repos/BepInEx/BepInEx/releases`
+pub async fn release(
+    client: &Client,
+    releases_url: &str,
+    token: Option<&str>,
+    tag: Option<&str>,
+) -> Result<Release> {
+    let url = match tag {
+        Some(tag) => format!("{releases_url}/tags/{tag}"),
+        None => format!("{releases_url}/latest"),
     };
 
-    Ok(urls)
+    get_url(client, &url, token).await
 }
 
-fn artifact_name(artifact: &Value) -> &str {
-    artifact.get("name").unwrap().as_str().unwrap()
+/// Most recent releases, including pre-releases, newest first
+pub async fn releases(
+    client: &Client,
+    releases_url: &str,
+    token: Option<&str>,
+) -> Result<Vec<Release>> {
+    get_url(client, &format!("{releases_url}?per_page=100"), token).await
 }
 
-fn artifact_size(artifact: &Value) -> u64 {
-    artifact.get("size_in_bytes").unwrap().as_u64().unwrap()
+/// Releases endpoint of a repository
+pub fn releases_url(api: &GhApi, owner: &str, repo: &str) -> String {
+    format!("{}/repos/{owner}/{repo}/releases", api.base_url)
 }
 
-fn artifact_dl_link(artifact: &Value) -> &str {
-    artifact
-        .get("archive_download_url")
-        .unwrap()
-        .as_str()
-        .unwrap()
+/// Download link of an artifact by its id, same as `archive_download_url` of the artifact
+pub fn artifact_zip_link(api: &GhApi, owner: &str, repo: &str, artifact_id: u64) -> String {
+    format!(
+        "{}/repos/{owner}/{repo}/actions/artifacts/{artifact_id}/zip",
+        api.base_url
+    )
 }
 
-pub async fn gh_api_client(
-    url: &str,
-    token: &str,
-) -> result::Result<RequestBuilder, reqwest::Error> {
-    let builder = reqwest::Client::builder()
-        .user_agent(env!("CARGO_PKG_NAME"))
-        .build()?
-        .get(url)
-        .header(ACCEPT, "application/vnd.github+json")
-        .bearer_auth(token)
-        .header("X-GitHub-Api-Version", "2022-11-28");
+/// Sends GET request with github api headers, waiting out rate limits
+///
+/// Non success status codes are turned into errors
+pub async fn send(client: &Client, url: &str, token: Option<&str>) -> Result<Response> {
+    let mut retries = 0;
+    loop {
+        let mut request = client
+            .get(url)
+            .header(ACCEPT, "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await.map_err(|source| GhApiError::Request {
+            url: url.to_string(),
+            source,
+        })?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        if let Some(wait) = rate_limit_wait(status, response.headers()) {
+            if retries >= MAX_RATE_LIMIT_RETRIES || wait > MAX_RATE_LIMIT_WAIT {
+                return Err(GhApiError::RateLimited {
+                    url: url.to_string(),
+                    wait,
+                });
+            }
+
+            warn!(
+                "github api rate limit hit for `{url}`, waiting {} seconds",
+                wait.as_secs()
+            );
+            tokio::time::sleep(wait).await;
+            retries += 1;
+            continue;
+        }
+
+        let message = response
+            .json::<ErrorBody>()
+            .await
+            .map(|body| body.message)
+            .unwrap_or_default();
+
+        return Err(GhApiError::Status {
+            url: url.to_string(),
+            status,
+            message,
+        });
+    }
+}
+
+/// How long to wait if the response is from hitting a rate limit
+fn rate_limit_wait(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    let header = |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+
+    if let Some(secs) = header("retry-after") {
+        return Some(Duration::from_secs(secs));
+    }
 
-    Ok(builder)
+    if header("x-ratelimit-remaining") == Some(0) {
+        if let Some(reset) = header("x-ratelimit-reset") {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            // reset time is in whole seconds, so wait an extra second
+            return Some(Duration::from_secs(reset.saturating_sub(now) + 1));
+        }
+    }
+
+    // 403 could also be a permission issue
+    (status == StatusCode::TOO_MANY_REQUESTS).then_some(SECONDARY_RATE_LIMIT_WAIT)
 }
 
-async fn get(
+async fn get<T: DeserializeOwned>(
+    api: &GhApi,
     owner: &str,
     repo: &str,
-    token: &str,
+    token: Option<&str>,
     path: &str,
-) -> result::Result<Value, reqwest::Error> {
-    gh_api_client(
-        &format!("{GITHUB_API_LINK}/repos/{owner}/{repo}/{path}"),
-        token,
-    )
-    .await?
-    .send()
-    .await?
-    .json()
-    .await
+    query: &[(&str, &str)],
+) -> Result<T> {
+    let url = format!("{}/repos/{owner}/{repo}/{path}", api.base_url);
+    let mut url = Url::parse(&url).map_err(|source| GhApiError::InvalidUrl { url, source })?;
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+
+    get_url(&api.client, url.as_str(), token).await
+}
+
+async fn get_url<T: DeserializeOwned>(
+    client: &Client,
+    url: &str,
+    token: Option<&str>,
+) -> Result<T> {
+    send(client, url, token)
+        .await?
+        .json()
+        .await
+        .map_err(|source| GhApiError::Decode {
+            url: url.to_string(),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{method, path},
+        Mock, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::download::test_server::{
+        fixture_zip, FakeGithub, FixtureArtifact, FixtureRun, OWNER, REPO, TOKEN, WORKFLOW,
+    };
+
+    async fn artifact_names(
+        github: &FakeGithub,
+        run: RunSelector<'_>,
+        filter: Option<ArtifactFilter<'_>>,
+    ) -> Result<Vec<String>> {
+        let artifacts =
+            latest_artifacts(&github.gh_api(), OWNER, REPO, TOKEN, WORKFLOW, run, filter).await?;
+        Ok(artifacts.into_iter().map(|a| a.name).collect())
+    }
+
+    #[tokio::test]
+    async fn latest_artifacts_skips_failed_runs() {
+        let github = FakeGithub::start().await;
+        github
+            .runs(&[
+                FixtureRun::failure(3, "main"),
+                FixtureRun::success(2, "main"),
+                FixtureRun::success(1, "main"),
+            ])
+            .await;
+        github
+            .artifacts(3, &[FixtureArtifact::new(30, "broken", fixture_zip(&[]))])
+            .await;
+        github
+            .artifacts(2, &[FixtureArtifact::new(20, "UniTAS", fixture_zip(&[]))])
+            .await;
+
+        let artifacts = latest_artifacts(
+            &github.gh_api(),
+            OWNER,
+            REPO,
+            TOKEN,
+            WORKFLOW,
+            RunSelector::Branch("main"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].name, "UniTAS");
+        assert_eq!(artifacts[0].run_id, 2);
+        assert_eq!(artifacts[0].id, 20);
+    }
+
+    #[tokio::test]
+    async fn latest_artifacts_matches_branch_and_commit() {
+        let github = FakeGithub::start().await;
+        github
+            .runs(&[
+                FixtureRun::success(3, "feature"),
+                FixtureRun {
+                    sha: "abcdef0000000000000000000000000000000000",
+                    ..FixtureRun::success(2, "main")
+                },
+                FixtureRun::success(1, "main"),
+            ])
+            .await;
+        github
+            .artifacts(3, &[FixtureArtifact::new(30, "feature", fixture_zip(&[]))])
+            .await;
+        github
+            .artifacts(2, &[FixtureArtifact::new(20, "commit", fixture_zip(&[]))])
+            .await;
+        github
+            .artifacts(1, &[FixtureArtifact::new(10, "oldest", fixture_zip(&[]))])
+            .await;
+
+        let names = artifact_names(&github, RunSelector::Branch("main"), None)
+            .await
+            .unwrap();
+        assert_eq!(names, ["commit"]);
+
+        let names = artifact_names(&github, RunSelector::Branch("feature"), None)
+            .await
+            .unwrap();
+        assert_eq!(names, ["feature"]);
+
+        let names = artifact_names(&github, RunSelector::HeadSha("abcdef"), None)
+            .await
+            .unwrap();
+        assert_eq!(names, ["commit"]);
+
+        let err = artifact_names(&github, RunSelector::Branch("missing"), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GhApiError::NoSuccessfulRun { .. }));
+    }
+
+    #[tokio::test]
+    async fn latest_artifacts_searches_next_pages() {
+        let github = FakeGithub::start().await;
+        github
+            .runs_page(1, 3, &[FixtureRun::failure(3, "main")])
+            .await;
+        github
+            .runs_page(
+                2,
+                3,
+                &[
+                    FixtureRun::failure(2, "main"),
+                    FixtureRun::success(1, "main"),
+                ],
+            )
+            .await;
+        github
+            .artifacts(1, &[FixtureArtifact::new(10, "UniTAS", fixture_zip(&[]))])
+            .await;
+
+        let names = artifact_names(&github, RunSelector::Branch("main"), None)
+            .await
+            .unwrap();
+        assert_eq!(names, ["UniTAS"]);
+    }
+
+    #[tokio::test]
+    async fn latest_artifacts_by_run_id() {
+        let github = FakeGithub::start().await;
+        github
+            .runs(&[
+                FixtureRun::failure(2, "main"),
+                FixtureRun::success(1, "main"),
+            ])
+            .await;
+        github
+            .artifacts(1, &[FixtureArtifact::new(10, "UniTAS", fixture_zip(&[]))])
+            .await;
+
+        let names = artifact_names(&github, RunSelector::Id(1), None)
+            .await
+            .unwrap();
+        assert_eq!(names, ["UniTAS"]);
+
+        let err = artifact_names(&github, RunSelector::Id(2), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GhApiError::RunNotSuccessful(2)));
+    }
+
+    #[tokio::test]
+    async fn latest_artifacts_filters() {
+        let github = FakeGithub::start().await;
+        github.runs(&[FixtureRun::success(1, "main")]).await;
+        github
+            .artifacts(
+                1,
+                &[
+                    FixtureArtifact::new(10, "UniTAS", fixture_zip(&[])),
+                    FixtureArtifact::new(11, "test-runner-linux", fixture_zip(&[])),
+                    FixtureArtifact::new(12, "unity_latest", fixture_zip(&[])),
+                    FixtureArtifact {
+                        expired: true,
+                        ..FixtureArtifact::new(13, "expired", fixture_zip(&[]))
+                    },
+                ],
+            )
+            .await;
+
+        let names = artifact_names(&github, RunSelector::Branch("main"), None)
+            .await
+            .unwrap();
+        assert_eq!(names, ["UniTAS", "test-runner-linux", "unity_latest"]);
+
+        let names = artifact_names(
+            &github,
+            RunSelector::Branch("main"),
+            Some(ArtifactFilter::TargetName("UniTAS")),
+        )
+        .await
+        .unwrap();
+        assert_eq!(names, ["UniTAS"]);
+
+        let names = artifact_names(
+            &github,
+            RunSelector::Branch("main"),
+            Some(ArtifactFilter::ExcludeNamePattern(
+                Regex::new("test-runner.*").unwrap(),
+            )),
+        )
+        .await
+        .unwrap();
+        assert_eq!(names, ["UniTAS", "unity_latest"]);
+    }
+
+    #[tokio::test]
+    async fn release_by_tag_and_latest() {
+        let github = FakeGithub::start().await;
+        github
+            .release("v2", true, &[("new.zip", fixture_zip(&[]))])
+            .await;
+        github
+            .release("v1", false, &[("old.zip", fixture_zip(&[]))])
+            .await;
+
+        let latest = release(&Client::new(), &github.releases_url(), None, None)
+            .await
+            .unwrap();
+        assert_eq!(latest.tag_name, "v2");
+        assert_eq!(latest.assets[0].name, "new.zip");
+
+        let tagged = release(&Client::new(), &github.releases_url(), None, Some("v1"))
+            .await
+            .unwrap();
+        assert_eq!(tagged.tag_name, "v1");
+
+        let Err(err) = release(&Client::new(), &github.releases_url(), None, Some("v0")).await
+        else {
+            panic!("release `v0` shouldn't exist");
+        };
+        assert!(
+            matches!(err, GhApiError::Status { status, .. } if status == StatusCode::NOT_FOUND)
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_api_url_is_an_error() {
+        let api = GhApi {
+            client: Client::new(),
+            base_url: "api.github.com".to_string(),
+        };
+
+        let result = pull_request_head_sha(&api, OWNER, REPO, TOKEN, 1).await;
+
+        assert!(matches!(result, Err(GhApiError::InvalidUrl { .. })));
+    }
+
+    #[tokio::test]
+    async fn send_retries_after_rate_limit() {
+        let github = FakeGithub::start().await;
+        Mock::given(method("GET"))
+            .and(path("/limited"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(github.server())
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/limited"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(github.server())
+            .await;
+
+        let response = send(&Client::new(), &format!("{}/limited", github.api()), None)
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
 }
@@ -0,0 +1,702 @@
+use std::{fmt::Display, path::PathBuf, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use indicatif::MultiProgress;
+use regex::Regex;
+use reqwest::Client;
+use tokio::fs;
+
+use super::{
+    dl_with_progress,
+    gh_api::{self, ArtifactFilter, GhApi, RunSelector},
+};
+use crate::{
+    cli::{BepInExVersion, GithubWorkflow, WorkflowBuild},
+    games::Backend,
+    lockfile::{LockedArtifact, LockedRelease},
+    Arch, Os,
+};
+
+/// Which artifact to look for in a source
+pub enum ArtifactQuery<'a> {
+    UniTas,
+    BepInEx {
+        version: &'a BepInExVersion,
+        backend: Backend,
+        os: &'a Os,
+        arch: &'a Arch,
+    },
+    /// Test games, names are the games the tests need
+    Games {
+        names: &'a [String],
+    },
+}
+
+impl Display for ArtifactQuery<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArtifactQuery::UniTas => write!(f, "UniTAS"),
+            ArtifactQuery::BepInEx {
+                version,
+                backend: Backend::Mono,
+                ..
+            } => write!(f, "BepInEx `{version}`"),
+            ArtifactQuery::BepInEx {
+                version,
+                backend: Backend::Il2Cpp,
+                ..
+            } => write!(f, "BepInEx `{version}` IL2CPP"),
+            ArtifactQuery::Games { .. } => write!(f, "test games"),
+        }
+    }
+}
+
+/// Artifact found in a source, ready to be fetched
+pub struct ResolvedArtifact {
+    pub name: String,
+    pub location: Location,
+    /// Download size if known, 0 if not
+    pub size: u64,
+    /// Release tag the artifact is from
+    pub tag: Option<String>,
+    /// Github actions run the artifact is from
+    pub origin: Option<ActionsOrigin>,
+}
+
+pub enum Location {
+    Url(String),
+    Path(PathBuf),
+}
+
+pub struct ActionsOrigin {
+    pub owner: String,
+    pub repo: String,
+    pub workflow: String,
+    pub run_id: u64,
+    pub artifact_id: u64,
+}
+
+pub enum Fetched {
+    Zip(Vec<u8>),
+    /// Already extracted artifact
+    Dir(PathBuf),
+}
+
+impl ResolvedArtifact {
+    pub fn from_locked_artifact(locked: &LockedArtifact, api: &GhApi) -> Self {
+        Self {
+            name: locked.name.to_owned(),
+            location: Location::Url(gh_api::artifact_zip_link(
+                api,
+                &locked.owner,
+                &locked.repo,
+                locked.artifact_id,
+            )),
+            size: locked.size,
+            tag: None,
+            origin: Some(ActionsOrigin {
+                owner: locked.owner.to_owned(),
+                repo: locked.repo.to_owned(),
+                workflow: locked.workflow.to_owned(),
+                run_id: locked.run_id,
+                artifact_id: locked.artifact_id,
+            }),
+        }
+    }
+
+    pub fn from_locked_release(locked: &LockedRelease) -> Self {
+        Self {
+            name: locked.asset.to_owned(),
+            location: Location::Url(locked.url.to_owned()),
+            size: locked.size,
+            tag: Some(locked.tag.to_owned()),
+            origin: None,
+        }
+    }
+}
+
+/// Where artifacts are acquired from
+pub trait ArtifactSource {
+    async fn resolve(&self, query: &ArtifactQuery<'_>) -> Result<Vec<ResolvedArtifact>>;
+
+    async fn fetch(&self, artifact: &ResolvedArtifact, pb: &MultiProgress) -> Result<Fetched>;
+}
+
+/// Source to use for an artifact, as given by the user
+#[derive(Clone)]
+pub enum SourceKind {
+    GithubActions,
+    GithubReleases,
+    Local(PathBuf),
+    Mirror(String),
+}
+
+impl FromStr for SourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let kind = match s {
+            "github-actions" => SourceKind::GithubActions,
+            "github-releases" => SourceKind::GithubReleases,
+            _ => {
+                if let Some(path) = s.strip_prefix("local:") {
+                    SourceKind::Local(PathBuf::from(path))
+                } else if let Some(url) = s.strip_prefix("mirror:") {
+                    SourceKind::Mirror(url.trim_end_matches('/').to_string())
+                } else {
+                    bail!("expected one of `github-actions`, `github-releases`, `local:<path>` or `mirror:<url>`");
+                }
+            }
+        };
+
+        Ok(kind)
+    }
+}
+
+impl Display for SourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceKind::GithubActions => write!(f, "github-actions"),
+            SourceKind::GithubReleases => write!(f, "github-releases"),
+            SourceKind::Local(path) => write!(f, "local:{}", path.display()),
+            SourceKind::Mirror(url) => write!(f, "mirror:{url}"),
+        }
+    }
+}
+
+/// Any of the source implementations, picked per artifact
+pub enum Source {
+    GithubActions(GithubActions),
+    GithubReleases(GithubReleases),
+    Local(LocalDir),
+    Mirror(HttpMirror),
+}
+
+impl ArtifactSource for Source {
+    async fn resolve(&self, query: &ArtifactQuery<'_>) -> Result<Vec<ResolvedArtifact>> {
+        match self {
+            Source::GithubActions(source) => source.resolve(query).await,
+            Source::GithubReleases(source) => source.resolve(query).await,
+            Source::Local(source) => source.resolve(query).await,
+            Source::Mirror(source) => source.resolve(query).await,
+        }
+    }
+
+    async fn fetch(&self, artifact: &ResolvedArtifact, pb: &MultiProgress) -> Result<Fetched> {
+        match self {
+            Source::GithubActions(source) => source.fetch(artifact, pb).await,
+            Source::GithubReleases(source) => source.fetch(artifact, pb).await,
+            Source::Local(source) => source.fetch(artifact, pb).await,
+            Source::Mirror(source) => source.fetch(artifact, pb).await,
+        }
+    }
+}
+
+/// Artifacts uploaded by github actions workflow runs
+pub struct GithubActions {
+    pub api: GhApi,
+    pub workflow: GithubWorkflow,
+    pub token: String,
+}
+
+impl ArtifactSource for GithubActions {
+    async fn resolve(&self, query: &ArtifactQuery<'_>) -> Result<Vec<ResolvedArtifact>> {
+        let GithubWorkflow {
+            owner,
+            repo,
+            workflow,
+            build,
+        } = &self.workflow;
+
+        let filter = match query {
+            ArtifactQuery::UniTas => ArtifactFilter::TargetName("UniTAS"),
+            ArtifactQuery::Games { .. } => ArtifactFilter::ExcludeNamePattern(
+                Regex::new(&format!("{}.*", env!("CARGO_PKG_NAME"))).unwrap(),
+            ),
+            ArtifactQuery::BepInEx { .. } => {
+                bail!("BepInEx can't be downloaded from github actions")
+            }
+        };
+
+        let pr_head_sha;
+        let run = match build {
+            WorkflowBuild::Branch(branch) => RunSelector::Branch(branch),
+            WorkflowBuild::RunId(id) => RunSelector::Id(*id),
+            WorkflowBuild::Commit(sha) => RunSelector::HeadSha(sha),
+            WorkflowBuild::PullRequest(number) => {
+                pr_head_sha =
+                    gh_api::pull_request_head_sha(&self.api, owner, repo, &self.token, *number)
+                        .await
+                        .with_context(|| format!("failed to find {query} build of pull request"))?;
+                RunSelector::HeadSha(&pr_head_sha)
+            }
+        };
+
+        let artifacts = gh_api::latest_artifacts(
+            &self.api,
+            owner,
+            repo,
+            &self.token,
+            workflow,
+            run,
+            Some(filter),
+        )
+        .await
+        .with_context(|| format!("failed to get latest build of {query}"))?;
+
+        Ok(artifacts
+            .into_iter()
+            .map(|artifact| ResolvedArtifact {
+                name: artifact.name,
+                location: Location::Url(artifact.link),
+                size: artifact.dl_len,
+                tag: None,
+                origin: Some(ActionsOrigin {
+                    owner: owner.to_owned(),
+                    repo: repo.to_owned(),
+                    workflow: workflow.to_owned(),
+                    run_id: artifact.run_id,
+                    artifact_id: artifact.id,
+                }),
+            })
+            .collect())
+    }
+
+    async fn fetch(&self, artifact: &ResolvedArtifact, pb: &MultiProgress) -> Result<Fetched> {
+        let Location::Url(link) = &artifact.location else {
+            bail!("github actions artifact `{}` has no link", artifact.name);
+        };
+
+        let response = gh_api::send(&self.api.client, link, Some(&self.token))
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to get response for downloading `{}` with link `{link}`",
+                    artifact.name
+                )
+            })?;
+
+        let bytes = dl_with_progress(response, artifact.size, pb, &artifact.name).await?;
+        Ok(Fetched::Zip(bytes))
+    }
+}
+
+/// Assets of github releases
+pub struct GithubReleases {
+    pub client: Client,
+    /// Releases endpoint of the repository
+    pub releases_url: String,
+}
+
+impl ArtifactSource for GithubReleases {
+    async fn resolve(&self, query: &ArtifactQuery<'_>) -> Result<Vec<ResolvedArtifact>> {
+        let (tag, asset_pattern) = match query {
+            ArtifactQuery::UniTas => (None, Regex::new(r"^UniTAS.*\.zip$").unwrap()),
+            ArtifactQuery::BepInEx {
+                version,
+                backend,
+                os,
+                arch,
+            } => {
+                let tag = match version {
+                    BepInExVersion::Latest => None,
+                    BepInExVersion::Tag(tag) => Some(tag.as_str()),
+                    BepInExVersion::Url(url) => return Ok(vec![direct_download(url)]),
+                };
+
+                let pattern = match backend {
+                    // BepInEx 5: BepInEx_linux_x64_5.4.23.2.zip
+                    // BepInEx 6: BepInEx-Unity.Mono-linux-x64-6.0.0-pre.2.zip
+                    Backend::Mono => {
+                        format!(r"^BepInEx[_-](Unity\.Mono[_-])?{os}[_-]{arch}[_-].+\.zip$")
+                    }
+                    // only BepInEx 6: BepInEx-Unity.IL2CPP-linux-x64-6.0.0-pre.2.zip
+                    Backend::Il2Cpp => {
+                        format!(r"^BepInEx[_-]Unity\.IL2CPP[_-]{os}[_-]{arch}[_-].+\.zip$")
+                    }
+                };
+
+                (tag, Regex::new(&pattern).unwrap())
+            }
+            ArtifactQuery::Games { .. } => {
+                bail!("test games can't be downloaded from github releases")
+            }
+        };
+
+        let release = match (query, tag) {
+            // BepInEx 6 is only released as pre-releases, which the latest release never is
+            (
+                ArtifactQuery::BepInEx {
+                    backend: Backend::Il2Cpp,
+                    ..
+                },
+                None,
+            ) => gh_api::releases(&self.client, &self.releases_url, None)
+                .await
+                .with_context(|| format!("failed to get releases for {query}"))?
+                .into_iter()
+                .find(|release| {
+                    release
+                        .assets
+                        .iter()
+                        .any(|asset| asset_pattern.is_match(&asset.name))
+                })
+                .with_context(|| {
+                    format!(
+                        "failed to find a release with an asset for {query} from `{}`",
+                        self.releases_url
+                    )
+                })?,
+            _ => gh_api::release(&self.client, &self.releases_url, None, tag)
+                .await
+                .with_context(|| format!("failed to get release of {query}"))?,
+        };
+
+        let Some(asset) = release
+            .assets
+            .iter()
+            .find(|asset| asset_pattern.is_match(&asset.name))
+        else {
+            let available = release
+                .assets
+                .iter()
+                .map(|asset| asset.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            bail!(
+                "failed to find asset for {query} in release `{}` from `{}`, available assets: [{available}]",
+                release.tag_name,
+                self.releases_url
+            );
+        };
+
+        Ok(vec![ResolvedArtifact {
+            name: asset.name.to_owned(),
+            location: Location::Url(asset.browser_download_url.to_owned()),
+            size: asset.size,
+            tag: Some(release.tag_name.to_owned()),
+            origin: None,
+        }])
+    }
+
+    async fn fetch(&self, artifact: &ResolvedArtifact, pb: &MultiProgress) -> Result<Fetched> {
+        fetch_url(&self.client, artifact, pb).await
+    }
+}
+
+/// Directory of BepInEx builds for games with the backend, in local and mirror sources
+fn bepinex_dir_name(backend: Backend) -> &'static str {
+    match backend {
+        Backend::Mono => "BepInEx",
+        Backend::Il2Cpp => "BepInEx-IL2CPP",
+    }
+}
+
+/// Local directory tree laid out as:
+///
+/// - `UniTAS.zip` or `UniTAS/`
+/// - `BepInEx/<version>.zip` or `BepInEx/<version>/`, version being `latest` or a tag
+/// - `BepInEx-IL2CPP/<version>.zip` or `BepInEx-IL2CPP/<version>/`, for IL2CPP games
+/// - `games/<name>.zip` or `games/<name>/`
+pub struct LocalDir {
+    pub root: PathBuf,
+}
+
+impl LocalDir {
+    /// Finds `<name>.zip` or `<name>/` in dir
+    fn find(&self, dir: PathBuf, name: &str) -> Result<ResolvedArtifact> {
+        let zip = dir.join(format!("{name}.zip"));
+        let path = if zip.is_file() {
+            zip
+        } else {
+            let path = dir.join(name);
+            if !path.is_dir() {
+                bail!("failed to find `{}` or `{}`", zip.display(), path.display());
+            }
+            path
+        };
+
+        Ok(ResolvedArtifact {
+            name: name.to_string(),
+            location: Location::Path(path),
+            size: 0,
+            tag: None,
+            origin: None,
+        })
+    }
+}
+
+impl ArtifactSource for LocalDir {
+    async fn resolve(&self, query: &ArtifactQuery<'_>) -> Result<Vec<ResolvedArtifact>> {
+        match query {
+            ArtifactQuery::UniTas => Ok(vec![self.find(self.root.clone(), "UniTAS")?]),
+            ArtifactQuery::BepInEx {
+                version, backend, ..
+            } => {
+                if let BepInExVersion::Url(url) = version {
+                    bail!("BepInEx `{url}` is a download URL, which a local directory can't serve");
+                }
+
+                let mut artifact = self.find(
+                    self.root.join(bepinex_dir_name(*backend)),
+                    &version.to_string(),
+                )?;
+                artifact.tag = Some(version.to_string());
+                Ok(vec![artifact])
+            }
+            ArtifactQuery::Games { names } => {
+                let games_dir = self.root.join("games");
+                names
+                    .iter()
+                    .map(|name| self.find(games_dir.clone(), name))
+                    .collect()
+            }
+        }
+    }
+
+    async fn fetch(&self, artifact: &ResolvedArtifact, _: &MultiProgress) -> Result<Fetched> {
+        let Location::Path(path) = &artifact.location else {
+            bail!("local artifact `{}` has no path", artifact.name);
+        };
+
+        if path.is_dir() {
+            return Ok(Fetched::Dir(path.to_owned()));
+        }
+
+        let bytes = fs::read(path)
+            .await
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        Ok(Fetched::Zip(bytes))
+    }
+}
+
+/// Http file server laid out like [`LocalDir`], but only with zip files
+pub struct HttpMirror {
+    pub client: Client,
+    pub base_url: String,
+}
+
+impl HttpMirror {
+    fn artifact(&self, path: &str, name: &str) -> ResolvedArtifact {
+        ResolvedArtifact {
+            name: name.to_string(),
+            location: Location::Url(format!("{}/{path}", self.base_url)),
+            size: 0,
+            tag: None,
+            origin: None,
+        }
+    }
+}
+
+impl ArtifactSource for HttpMirror {
+    async fn resolve(&self, query: &ArtifactQuery<'_>) -> Result<Vec<ResolvedArtifact>> {
+        let artifacts = match query {
+            ArtifactQuery::UniTas => vec![self.artifact("UniTAS.zip", "UniTAS")],
+            ArtifactQuery::BepInEx {
+                version, backend, ..
+            } => {
+                if let BepInExVersion::Url(url) = version {
+                    return Ok(vec![direct_download(url)]);
+                }
+
+                let dir = bepinex_dir_name(*backend);
+                let mut artifact =
+                    self.artifact(&format!("{dir}/{version}.zip"), &version.to_string());
+                artifact.tag = Some(version.to_string());
+                vec![artifact]
+            }
+            // a plain file server can't be listed, so only look for what's needed
+            ArtifactQuery::Games { names } => names
+                .iter()
+                .map(|name| self.artifact(&format!("games/{name}.zip"), name))
+                .collect(),
+        };
+
+        Ok(artifacts)
+    }
+
+    async fn fetch(&self, artifact: &ResolvedArtifact, pb: &MultiProgress) -> Result<Fetched> {
+        fetch_url(&self.client, artifact, pb).await
+    }
+}
+
+fn direct_download(url: &str) -> ResolvedArtifact {
+    let name = url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(url);
+
+    ResolvedArtifact {
+        name: name.to_string(),
+        location: Location::Url(url.to_owned()),
+        size: 0,
+        tag: Some(name.to_string()),
+        origin: None,
+    }
+}
+
+/// Plain download without any github api headers or token
+pub async fn fetch_url(
+    client: &Client,
+    artifact: &ResolvedArtifact,
+    pb: &MultiProgress,
+) -> Result<Fetched> {
+    let Location::Url(url) = &artifact.location else {
+        bail!("artifact `{}` has no download URL", artifact.name);
+    };
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("failed to GET request for download `{url}`"))?;
+
+    // direct downloads don't know the size beforehand
+    let size = match artifact.size {
+        0 => response.content_length().unwrap_or_default(),
+        size => size,
+    };
+
+    let bytes = dl_with_progress(response, size, pb, &artifact.name).await?;
+    Ok(Fetched::Zip(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::download::test_server::{fixture_zip, hidden_progress};
+
+    fn bepinex_query(version: &BepInExVersion) -> ArtifactQuery<'_> {
+        ArtifactQuery::BepInEx {
+            version,
+            backend: Backend::Mono,
+            os: &Os::Linux,
+            arch: &Arch::X64,
+        }
+    }
+
+    fn names(artifacts: &[ResolvedArtifact]) -> Vec<&str> {
+        artifacts.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn local_dir_serves_dirs_and_zips() {
+        let root = tempfile::tempdir().unwrap();
+        let zip = fixture_zip(&[("file", b"zipped")]);
+        fs::create_dir_all(root.path().join("UniTAS")).unwrap();
+        fs::create_dir_all(root.path().join("BepInEx")).unwrap();
+        fs::write(root.path().join("BepInEx").join("v5.zip"), &zip).unwrap();
+        for game in ["dir_game", "unselected"] {
+            fs::create_dir_all(root.path().join("games").join(game)).unwrap();
+        }
+        fs::write(root.path().join("games").join("zip_game.zip"), &zip).unwrap();
+        let local = LocalDir {
+            root: root.path().to_path_buf(),
+        };
+
+        let unitas = local.resolve(&ArtifactQuery::UniTas).await.unwrap();
+        let fetched = local.fetch(&unitas[0], &hidden_progress()).await.unwrap();
+        assert!(matches!(fetched, Fetched::Dir(dir) if dir == root.path().join("UniTAS")));
+
+        let version = BepInExVersion::Tag("v5".to_string());
+        let bepinex = local.resolve(&bepinex_query(&version)).await.unwrap();
+        assert_eq!(bepinex[0].tag.as_deref(), Some("v5"));
+        let fetched = local.fetch(&bepinex[0], &hidden_progress()).await.unwrap();
+        assert!(matches!(fetched, Fetched::Zip(bytes) if bytes == zip));
+
+        let selected = ["zip_game".to_string(), "dir_game".to_string()];
+        let games = local
+            .resolve(&ArtifactQuery::Games { names: &selected })
+            .await
+            .unwrap();
+        assert_eq!(names(&games), ["zip_game", "dir_game"]);
+
+        let missing = ["missing".to_string()];
+        assert!(local
+            .resolve(&ArtifactQuery::Games { names: &missing })
+            .await
+            .is_err());
+        assert!(local
+            .resolve(&bepinex_query(&BepInExVersion::Latest))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn local_dir_cant_serve_bepinex_urls() {
+        let root = tempfile::tempdir().unwrap();
+        let local = LocalDir {
+            root: root.path().to_path_buf(),
+        };
+        let version = BepInExVersion::Url("https://builds.bepinex.dev/BepInEx.zip".to_string());
+
+        let Err(err) = local.resolve(&bepinex_query(&version)).await else {
+            panic!("a local directory shouldn't serve BepInEx download URLs");
+        };
+
+        assert!(
+            err.to_string()
+                .contains("which a local directory can't serve"),
+            "{err:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn http_mirror_serves_zips() {
+        let server = MockServer::start().await;
+        let zip = fixture_zip(&[("file", b"zipped")]);
+        for file in [
+            "/UniTAS.zip",
+            "/BepInEx/latest.zip",
+            "/games/unity_latest.zip",
+        ] {
+            Mock::given(method("GET"))
+                .and(path(file))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(zip.clone()))
+                .mount(&server)
+                .await;
+        }
+        let mirror = HttpMirror {
+            client: Client::new(),
+            base_url: server.uri(),
+        };
+
+        let unitas = mirror.resolve(&ArtifactQuery::UniTas).await.unwrap();
+        let fetched = mirror.fetch(&unitas[0], &hidden_progress()).await.unwrap();
+        assert!(matches!(fetched, Fetched::Zip(bytes) if bytes == zip));
+
+        let bepinex = mirror
+            .resolve(&bepinex_query(&BepInExVersion::Latest))
+            .await
+            .unwrap();
+        assert_eq!(bepinex[0].tag.as_deref(), Some("latest"));
+        assert!(mirror.fetch(&bepinex[0], &hidden_progress()).await.is_ok());
+
+        let selected = ["unity_latest".to_string(), "missing".to_string()];
+        let games = mirror
+            .resolve(&ArtifactQuery::Games { names: &selected })
+            .await
+            .unwrap();
+        assert_eq!(names(&games), ["unity_latest", "missing"]);
+        assert!(mirror.fetch(&games[0], &hidden_progress()).await.is_ok());
+        assert!(mirror.fetch(&games[1], &hidden_progress()).await.is_err());
+
+        // downloaded as is instead of from the mirror
+        let url = format!("{}/BepInEx/latest.zip", server.uri());
+        let direct = mirror
+            .resolve(&bepinex_query(&BepInExVersion::Url(url.clone())))
+            .await
+            .unwrap();
+        assert!(matches!(&direct[0].location, Location::Url(location) if *location == url));
+        assert_eq!(direct[0].tag.as_deref(), Some("latest.zip"));
+    }
+}
@@ -0,0 +1,280 @@
+//! Local stand-in for the github api, serving workflows, runs, artifacts and releases from fixtures
+
+use std::io::{Cursor, Write};
+
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use reqwest::Client;
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use super::gh_api::GhApi;
+
+pub const OWNER: &str = "owner";
+pub const REPO: &str = "repo";
+pub const WORKFLOW: &str = "build.yml";
+pub const WORKFLOW_ID: u64 = 42;
+pub const TOKEN: &str = "token";
+
+pub struct FixtureRun {
+    pub id: u64,
+    pub branch: &'static str,
+    pub sha: &'static str,
+    pub conclusion: &'static str,
+}
+
+impl FixtureRun {
+    pub fn success(id: u64, branch: &'static str) -> Self {
+        Self {
+            id,
+            branch,
+            sha: "0000000000000000000000000000000000000000",
+            conclusion: "success",
+        }
+    }
+
+    pub fn failure(id: u64, branch: &'static str) -> Self {
+        Self {
+            conclusion: "failure",
+            ..Self::success(id, branch)
+        }
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "head_branch": self.branch,
+            "head_sha": self.sha,
+            "status": "completed",
+            "conclusion": self.conclusion,
+        })
+    }
+}
+
+pub struct FixtureArtifact {
+    pub id: u64,
+    pub name: &'static str,
+    pub zip: Vec<u8>,
+    pub expired: bool,
+}
+
+impl FixtureArtifact {
+    pub fn new(id: u64, name: &'static str, zip: Vec<u8>) -> Self {
+        Self {
+            id,
+            name,
+            zip,
+            expired: false,
+        }
+    }
+}
+
+/// Release asset, name and zip contents
+pub type FixtureAsset<'a> = (&'a str, Vec<u8>);
+
+/// Github api on a local http server
+pub struct FakeGithub {
+    server: MockServer,
+}
+
+impl FakeGithub {
+    /// Starts the server with [`WORKFLOW`] of [`OWNER`]/[`REPO`] already registered
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/repos/{OWNER}/{REPO}/actions/workflows/{WORKFLOW}"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": WORKFLOW_ID,
+                "name": "build",
+            })))
+            .mount(&server)
+            .await;
+
+        Self { server }
+    }
+
+    /// Base URL to use as the github api
+    pub fn api(&self) -> String {
+        self.server.uri()
+    }
+
+    pub fn gh_api(&self) -> GhApi {
+        GhApi {
+            client: Client::new(),
+            base_url: self.api(),
+        }
+    }
+
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Serves one page of the workflow runs list, newest run first
+    pub async fn runs_page(&self, page: u64, total_count: u64, runs: &[FixtureRun]) {
+        let runs = runs.iter().map(FixtureRun::json).collect::<Vec<_>>();
+
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/repos/{OWNER}/{REPO}/actions/workflows/{WORKFLOW_ID}/runs"
+            )))
+            .and(query_param("page", page.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "total_count": total_count,
+                "workflow_runs": runs,
+            })))
+            .mount(&self.server)
+            .await;
+
+        for run in &runs {
+            Mock::given(method("GET"))
+                .and(path(format!(
+                    "/repos/{OWNER}/{REPO}/actions/runs/{}",
+                    run["id"]
+                )))
+                .respond_with(ResponseTemplate::new(200).set_body_json(run))
+                .mount(&self.server)
+                .await;
+        }
+    }
+
+    pub async fn runs(&self, runs: &[FixtureRun]) {
+        self.runs_page(1, runs.len() as u64, runs).await;
+    }
+
+    /// Serves artifacts of a run, and their zip downloads
+    pub async fn artifacts(&self, run_id: u64, artifacts: &[FixtureArtifact]) {
+        let listed = artifacts
+            .iter()
+            .map(|artifact| {
+                json!({
+                    "id": artifact.id,
+                    "name": artifact.name,
+                    "size_in_bytes": artifact.zip.len(),
+                    "archive_download_url": self.artifact_url(artifact.id),
+                    "expired": artifact.expired,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/repos/{OWNER}/{REPO}/actions/runs/{run_id}/artifacts"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "total_count": listed.len(),
+                "artifacts": listed,
+            })))
+            .mount(&self.server)
+            .await;
+
+        for artifact in artifacts {
+            Mock::given(method("GET"))
+                .and(path(format!(
+                    "/repos/{OWNER}/{REPO}/actions/artifacts/{}/zip",
+                    artifact.id
+                )))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(artifact.zip.clone()))
+                .mount(&self.server)
+                .await;
+        }
+    }
+
+    fn artifact_url(&self, id: u64) -> String {
+        format!(
+            "{}/repos/{OWNER}/{REPO}/actions/artifacts/{id}/zip",
+            self.api()
+        )
+    }
+
+    /// Releases endpoint of [`OWNER`]/[`REPO`]
+    pub fn releases_url(&self) -> String {
+        format!("{}/repos/{OWNER}/{REPO}/releases", self.api())
+    }
+
+    /// Serves a release by its tag, and as the latest release if `latest` is set
+    pub async fn release(&self, tag: &str, latest: bool, assets: &[FixtureAsset<'_>]) {
+        let body = self.release_json(tag, assets);
+
+        let mut paths = vec![format!("/repos/{OWNER}/{REPO}/releases/tags/{tag}")];
+        if latest {
+            paths.push(format!("/repos/{OWNER}/{REPO}/releases/latest"));
+        }
+        for release_path in paths {
+            Mock::given(method("GET"))
+                .and(path(release_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(&body))
+                .mount(&self.server)
+                .await;
+        }
+
+        self.release_downloads(tag, assets).await;
+    }
+
+    /// Serves the list of releases, given newest first
+    pub async fn releases(&self, releases: &[(&str, &[FixtureAsset<'_>])]) {
+        let listed = releases
+            .iter()
+            .map(|(tag, assets)| self.release_json(tag, assets))
+            .collect::<Vec<_>>();
+
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/{OWNER}/{REPO}/releases")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(listed))
+            .mount(&self.server)
+            .await;
+
+        for (tag, assets) in releases {
+            self.release_downloads(tag, assets).await;
+        }
+    }
+
+    fn release_json(&self, tag: &str, assets: &[FixtureAsset<'_>]) -> Value {
+        let listed = assets
+            .iter()
+            .map(|(name, zip)| {
+                json!({
+                    "name": name,
+                    "size": zip.len(),
+                    "browser_download_url": format!("{}/downloads/{tag}/{name}", self.api()),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "tag_name": tag,
+            "assets": listed,
+        })
+    }
+
+    async fn release_downloads(&self, tag: &str, assets: &[FixtureAsset<'_>]) {
+        for (name, zip) in assets {
+            Mock::given(method("GET"))
+                .and(path(format!("/downloads/{tag}/{name}")))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(zip.clone()))
+                .mount(&self.server)
+                .await;
+        }
+    }
+}
+
+/// Zip archive with the given files
+pub fn fixture_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        zip.start_file(*name, SimpleFileOptions::default().unix_permissions(0o644))
+            .unwrap();
+        zip.write_all(contents).unwrap();
+    }
+
+    zip.finish().unwrap().into_inner()
+}
+
+pub fn hidden_progress() -> MultiProgress {
+    MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+}
@@ -1,4 +1,8 @@
-use std::path::Path;
+use std::{
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
 
 use tokio::{fs, io};
 
@@ -33,3 +37,65 @@ pub fn copy_dir_all_blocking(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io
     }
     Ok(())
 }
+
+/// Waits until sizes of the files stop changing, returns false if they still change after `timeout`
+///
+/// Missing files count as size 0, so a file created late is waited for as well
+pub fn wait_for_stable_size(paths: &[&Path], timeout: Duration) -> bool {
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+    // unchanged for this many polls in a row
+    const STABLE_POLLS: u32 = 3;
+
+    let sizes = || {
+        paths
+            .iter()
+            .map(|path| std::fs::metadata(path).map_or(0, |metadata| metadata.len()))
+            .collect::<Vec<_>>()
+    };
+
+    let start = Instant::now();
+    let mut last = sizes();
+    let mut stable_polls = 0;
+    while stable_polls < STABLE_POLLS {
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+
+        let current = sizes();
+        if current == last {
+            stable_polls += 1;
+        } else {
+            stable_polls = 0;
+            last = current;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn waits_for_file_to_stop_growing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("UniTAS.log");
+        let mut file = std::fs::File::create(&path).unwrap();
+
+        let writer = thread::spawn(move || {
+            for _ in 0..5 {
+                writeln!(file, "line").unwrap();
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+        assert!(wait_for_stable_size(&[&path], Duration::from_secs(5)));
+        writer.join().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "line\n".repeat(5));
+        assert!(!wait_for_stable_size(&[&path], Duration::ZERO));
+    }
+}
@@ -0,0 +1,331 @@
+//! Parses BepInEx logs like `UniTAS.log` and the Unity player log `stdout.log` into events
+
+use std::{fmt::Display, fs, io::ErrorKind, path::Path, sync::LazyLock};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// BepInEx entry `[Level : Source] message`
+static BEPINEX_ENTRY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\[(Fatal|Error|Warning|Message|Info|Debug)\s*:\s*([^\]]*?)\s*\] ?(.*)$").unwrap()
+});
+/// `SomeException: message`, as Unity and .NET print them
+static EXCEPTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:Unhandled [Ee]xception\. )?([A-Za-z_][\w.`]*Exception)(?:: (.*))?$").unwrap()
+});
+
+/// What errors in game logs which the game doesn't allow do to the result
+#[derive(ValueEnum, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogPolicy {
+    /// Each one is a failed test
+    #[default]
+    Fail,
+    /// Printed, the result is unaffected
+    Warn,
+    /// Only recorded in the report
+    Ignore,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Fatal,
+    Error,
+    Warning,
+    Message,
+    Info,
+    Debug,
+}
+
+impl Level {
+    fn parse(level: &str) -> Option<Self> {
+        Some(match level {
+            "Fatal" => Level::Fatal,
+            "Error" => Level::Error,
+            "Warning" => Level::Warning,
+            "Message" => Level::Message,
+            "Info" => Level::Info,
+            "Debug" => Level::Debug,
+            _ => return None,
+        })
+    }
+
+    /// Error or worse
+    pub fn is_error(&self) -> bool {
+        *self <= Level::Error
+    }
+}
+
+/// Log file an event is from
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogFile {
+    #[serde(rename = "UniTAS.log")]
+    UniTas,
+    #[serde(rename = "stdout.log")]
+    Player,
+}
+
+impl Display for LogFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LogFile::UniTas => "UniTAS.log",
+                LogFile::Player => "stdout.log",
+            }
+        )
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LogEvent {
+    pub file: LogFile,
+    /// Line number the event starts at, from 1
+    pub line: usize,
+    pub level: Level,
+    /// BepInEx log source like `UniTAS` or `Unity Log`, unset for Unity player log entries
+    pub source: Option<String>,
+    pub message: String,
+    /// Type of the exception, if the event is one
+    pub exception: Option<String>,
+    pub stack_trace: Vec<String>,
+}
+
+impl LogEvent {
+    /// Errors, fatal entries and exceptions
+    pub fn is_error(&self) -> bool {
+        self.level.is_error() || self.exception.is_some()
+    }
+
+    /// Whether any of `patterns` is found in the message
+    pub fn matches_any(&self, patterns: &[Regex]) -> bool {
+        patterns
+            .iter()
+            .any(|pattern| pattern.is_match(&self.message))
+    }
+
+    /// Log source for BepInEx entries, the log file otherwise
+    pub fn source_name(&self) -> String {
+        match &self.source {
+            Some(source) => source.to_owned(),
+            None => self.file.to_string(),
+        }
+    }
+}
+
+impl Display for LogEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{} [{:?}: {}] {}",
+            self.file,
+            self.line,
+            self.level,
+            self.source_name(),
+            self.message
+        )?;
+        for frame in &self.stack_trace {
+            write!(f, "\n  {frame}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a log file, a missing file has no events
+pub fn parse_file(path: &Path, file: LogFile) -> Result<Vec<LogEvent>> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read log `{}`", path.display()))
+        }
+    };
+
+    Ok(parse(&String::from_utf8_lossy(&contents), file))
+}
+
+/// Parses BepInEx entries `[Level : Source] message`, with the lines after an entry being part of it,
+/// and Unity player log exceptions `SomeException: message` followed by their stack trace
+pub fn parse(contents: &str, file: LogFile) -> Vec<LogEvent> {
+    let bepinex_entry = &*BEPINEX_ENTRY;
+    let exception = &*EXCEPTION;
+
+    let mut events: Vec<LogEvent> = Vec::new();
+    // whether following lines can still belong to the last event
+    let mut open = false;
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+
+        if let Some(captures) = bepinex_entry.captures(line) {
+            let message = captures[3].to_string();
+            events.push(LogEvent {
+                file,
+                line: line_number,
+                level: Level::parse(&captures[1]).unwrap(),
+                source: Some(captures[2].to_string()),
+                exception: exception
+                    .captures(&message)
+                    .map(|captures| captures[1].to_string()),
+                message,
+                stack_trace: Vec::new(),
+            });
+            open = true;
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if let Some(event) = events.last_mut().filter(|_| open) {
+            if is_stack_frame(trimmed) {
+                event.stack_trace.push(trimmed.to_string());
+                continue;
+            }
+            // BepInEx entries span lines until the next entry
+            if event.source.is_some() && !trimmed.is_empty() {
+                if event.exception.is_none() {
+                    event.exception = exception
+                        .captures(trimmed)
+                        .map(|captures| captures[1].to_string());
+                }
+                event.message.push('\n');
+                event.message.push_str(line);
+                continue;
+            }
+        }
+        open = false;
+
+        if let Some(captures) = exception.captures(trimmed) {
+            events.push(LogEvent {
+                file,
+                line: line_number,
+                level: Level::Error,
+                source: None,
+                message: trimmed.to_string(),
+                exception: Some(captures[1].to_string()),
+                stack_trace: Vec::new(),
+            });
+            open = true;
+        } else if trimmed == "Crash!!!" {
+            events.push(LogEvent {
+                file,
+                line: line_number,
+                level: Level::Fatal,
+                source: None,
+                message: trimmed.to_string(),
+                exception: None,
+                stack_trace: Vec::new(),
+            });
+            open = true;
+        }
+    }
+
+    events
+}
+
+/// Level of a single line, for lines which start a BepInEx entry or an exception
+pub fn line_level(line: &str) -> Option<Level> {
+    if let Some(captures) = BEPINEX_ENTRY.captures(line) {
+        return Level::parse(&captures[1]);
+    }
+    EXCEPTION.is_match(line.trim()).then_some(Level::Error)
+}
+
+/// Mono `at Type.Method ()` frames, Unity `Type:Method ()` frames and `Rethrow as` lines
+fn is_stack_frame(line: &str) -> bool {
+    line.starts_with("at ")
+        || line.starts_with("Rethrow as ")
+        || line.starts_with("--- End of")
+        || line.starts_with("(Filename:")
+        || line.split_once(':').is_some_and(|(ty, method)| {
+            !ty.is_empty() && !ty.contains(' ') && !method.starts_with(' ') && method.contains('(')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bepinex_entries() {
+        let log = "[Info   :   BepInEx] Loading [UniTAS 0.1.0]
+[Message:    UniTAS] init
+[Error  :    UniTAS] patch failed
+System.NullReferenceException: Object reference not set to an instance of an object
+  at UniTAS.Patches.Foo.Prefix () [0x00000] in <abc>:0
+[Warning: Unity Log] careful
+";
+        let events = parse(log, LogFile::UniTas);
+
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].level, Level::Info);
+        assert_eq!(events[0].source.as_deref(), Some("BepInEx"));
+        assert_eq!(events[0].message, "Loading [UniTAS 0.1.0]");
+
+        let error = &events[2];
+        assert_eq!(error.line, 3);
+        assert!(error.is_error());
+        assert_eq!(
+            error.exception.as_deref(),
+            Some("System.NullReferenceException")
+        );
+        assert_eq!(
+            error.stack_trace,
+            ["at UniTAS.Patches.Foo.Prefix () [0x00000] in <abc>:0"]
+        );
+
+        assert_eq!(events[3].source.as_deref(), Some("Unity Log"));
+        assert!(!events[3].is_error());
+    }
+
+    #[test]
+    fn parses_player_log_exceptions() {
+        let log = "Initialize engine version: 6000.0.44f1
+NullReferenceException: Object reference not set to an instance of an object
+  at Game.Update () [0x00001] in <def>:0
+UnityEngine.Debug:LogException(Exception)
+(Filename: <def> Line: 0)
+
+Setting up 4 worker threads
+";
+        let events = parse(log, LogFile::Player);
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.line, 2);
+        assert_eq!(event.source, None);
+        assert_eq!(event.exception.as_deref(), Some("NullReferenceException"));
+        assert_eq!(event.stack_trace.len(), 3);
+    }
+
+    #[test]
+    fn level_of_line() {
+        assert_eq!(
+            line_level("[Warning: Unity Log] careful"),
+            Some(Level::Warning)
+        );
+        assert_eq!(
+            line_level("NullReferenceException: Object reference not set"),
+            Some(Level::Error)
+        );
+        assert_eq!(line_level("  at Game.Update () [0x00001] in <def>:0"), None);
+    }
+
+    #[test]
+    fn allowed_errors_match_message() {
+        let log = "[Error  :    UniTAS] failed to patch Foo
+[Error  : Unity Log] Shader Hidden/Blit is not supported on this GPU
+";
+        let events = parse(log, LogFile::UniTas);
+        let allowed = [Regex::new("^Shader .* is not supported").unwrap()];
+
+        assert!(!events[0].matches_any(&allowed));
+        assert!(events[1].matches_any(&allowed));
+        assert_eq!(events[1].source_name(), "Unity Log");
+        assert_eq!(
+            events[1].to_string(),
+            "UniTAS.log:2 [Error: Unity Log] Shader Hidden/Blit is not supported on this GPU"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{ini, Arch, Os};
+
+/// Default registry, games declared by config or found in `Clients/` are added on top of it
+const BUILTIN_GAMES: &str = include_str!("games.toml");
+
+const CLIENTS_DIR_NAME: &str = "Clients";
+const DEFAULT_EXE_NAME: &str = "build";
+const DEFAULT_ENTRYPOINT: &str = "general";
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Mono,
+    Il2Cpp,
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Backend::Mono => "Mono",
+                Backend::Il2Cpp => "IL2CPP",
+            }
+        )
+    }
+}
+
+/// Test game as declared in the registry, every field is optional so declarations can be layered
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameEntry {
+    pub os: Option<Vec<Os>>,
+    pub arch: Option<Vec<Arch>>,
+    pub unity_version: Option<String>,
+    /// Executable name without the extension
+    pub exe_name: Option<String>,
+    pub backend: Option<Backend>,
+    /// Names of test functions to run in order
+    pub entrypoints: Option<Vec<String>>,
+    /// Names of movies the `bench` command plays
+    pub benchmarks: Option<Vec<String>>,
+    /// Extra arguments the game is launched with
+    pub args: Option<Vec<String>>,
+    /// Extra environment variables the game is launched with
+    pub env: Option<BTreeMap<String, String>>,
+    /// UniTAS.cfg settings, on top of the ones for every game
+    pub unitas_cfg: Option<ini::Settings>,
+    /// BepInEx.cfg settings, on top of the ones for every game
+    pub bepinex_cfg: Option<ini::Settings>,
+    /// Regexes of errors the game is expected to log, searched for in the message of each error
+    pub allowed_log_errors: Option<Vec<String>>,
+    /// Local build of the game, used instead of downloading it
+    pub path: Option<PathBuf>,
+}
+
+impl GameEntry {
+    /// Fields set in `other` replace the ones in `self`
+    fn overlay(self, other: &GameEntry) -> Self {
+        let other = other.clone();
+        Self {
+            os: other.os.or(self.os),
+            arch: other.arch.or(self.arch),
+            unity_version: other.unity_version.or(self.unity_version),
+            exe_name: other.exe_name.or(self.exe_name),
+            backend: other.backend.or(self.backend),
+            entrypoints: other.entrypoints.or(self.entrypoints),
+            benchmarks: other.benchmarks.or(self.benchmarks),
+            args: other.args.or(self.args),
+            env: other.env.or(self.env),
+            unitas_cfg: other.unitas_cfg.or(self.unitas_cfg),
+            bepinex_cfg: other.bepinex_cfg.or(self.bepinex_cfg),
+            allowed_log_errors: other.allowed_log_errors.or(self.allowed_log_errors),
+            path: other.path.or(self.path),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegistryFile {
+    #[serde(default)]
+    games: BTreeMap<String, GameEntry>,
+}
+
+/// Fully resolved test game
+#[derive(Clone)]
+pub struct GameSpec {
+    pub name: String,
+    pub os: Vec<Os>,
+    pub arch: Vec<Arch>,
+    pub unity_version: Option<String>,
+    pub exe_name: String,
+    pub backend: Backend,
+    pub entrypoints: Vec<String>,
+    pub benchmarks: Vec<String>,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub unitas_cfg: ini::Settings,
+    pub bepinex_cfg: ini::Settings,
+    pub allowed_log_errors: Vec<String>,
+}
+
+impl GameSpec {
+    pub fn supports(&self, os: &Os, arch: &Arch) -> bool {
+        self.os.contains(os) && self.arch.contains(arch)
+    }
+
+    /// File name of the game executable
+    pub fn exe_file(&self, os: &Os) -> String {
+        match os {
+            Os::Linux => format!("{}.x86_64", self.exe_name),
+            Os::Windows => format!("{}.exe", self.exe_name),
+        }
+    }
+}
+
+/// Builds the registry from the builtin games, games discovered in `clients_dir`, then games from config
+pub fn registry(
+    clients_dir: Option<&Path>,
+    config_games: &BTreeMap<String, GameEntry>,
+) -> Result<Vec<GameSpec>> {
+    let builtin: RegistryFile =
+        toml::from_str(BUILTIN_GAMES).expect("builtin games registry should be valid");
+
+    let mut entries = match clients_dir {
+        Some(dir) => discover_clients(dir)?,
+        None => BTreeMap::new(),
+    };
+
+    for layer in [&builtin.games, config_games] {
+        for (name, entry) in layer {
+            let merged = entries.remove(name).unwrap_or_default().overlay(entry);
+            entries.insert(name.to_owned(), merged);
+        }
+    }
+
+    Ok(entries
+        .into_iter()
+        .map(|(name, entry)| GameSpec {
+            name,
+            os: entry.os.unwrap_or_else(|| vec![Os::Linux, Os::Windows]),
+            arch: entry.arch.unwrap_or_else(|| vec![Arch::X64]),
+            unity_version: entry.unity_version,
+            exe_name: entry
+                .exe_name
+                .unwrap_or_else(|| DEFAULT_EXE_NAME.to_string()),
+            backend: entry.backend.unwrap_or(Backend::Mono),
+            entrypoints: entry
+                .entrypoints
+                .unwrap_or_else(|| vec![DEFAULT_ENTRYPOINT.to_string()]),
+            benchmarks: entry.benchmarks.unwrap_or_default(),
+            args: entry.args.unwrap_or_default(),
+            env: entry.env.unwrap_or_default(),
+            unitas_cfg: entry.unitas_cfg.unwrap_or_default(),
+            bepinex_cfg: entry.bepinex_cfg.unwrap_or_default(),
+            allowed_log_errors: entry.allowed_log_errors.unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// `Clients/` in the root of the git repository the current directory is in
+pub fn find_clients_dir() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    let repo_root = cwd.ancestors().find(|dir| dir.join(".git").exists())?;
+    let clients_dir = repo_root.join(CLIENTS_DIR_NAME);
+
+    clients_dir.is_dir().then_some(clients_dir)
+}
+
+/// Unity projects in `dir`, with the Unity version and scripting backend read from project settings
+fn discover_clients(dir: &Path) -> Result<BTreeMap<String, GameEntry>> {
+    let read_dir = fs::read_dir(dir)
+        .with_context(|| format!("failed to read clients directory `{}`", dir.display()))?;
+
+    let mut entries = BTreeMap::new();
+    for entry in read_dir {
+        let project_dir = entry?.path();
+        let settings_dir = project_dir.join("ProjectSettings");
+        let Ok(version) = fs::read_to_string(settings_dir.join("ProjectVersion.txt")) else {
+            continue;
+        };
+        let Some(name) = project_dir.file_name() else {
+            continue;
+        };
+        let name = name.to_string_lossy().to_string();
+
+        let unity_version = version
+            .lines()
+            .find_map(|line| line.strip_prefix("m_EditorVersion:"))
+            .map(|version| version.trim().to_string());
+        let backend = fs::read_to_string(settings_dir.join("ProjectSettings.asset"))
+            .ok()
+            .map(|settings| standalone_backend(&settings));
+
+        debug!(
+            "discovered test game `{name}` with Unity {unity_version:?} and backend {backend:?}"
+        );
+
+        entries.insert(
+            name,
+            GameEntry {
+                unity_version,
+                backend,
+                ..Default::default()
+            },
+        );
+    }
+
+    Ok(entries)
+}
+
+/// Scripting backend of a game build, from the files unity ships with it
+///
+/// IL2CPP builds have `GameAssembly.so` or `GameAssembly.dll` next to the executable and `<exe>_Data/il2cpp_data`,
+/// mono builds have `<exe>_Data/Managed`
+pub fn detect_backend(game_dir: &Path, exe_name: &str) -> Option<Backend> {
+    let data_dir = game_dir.join(format!("{exe_name}_Data"));
+
+    if ["GameAssembly.so", "GameAssembly.dll"]
+        .iter()
+        .any(|file| game_dir.join(file).is_file())
+        || data_dir.join("il2cpp_data").is_dir()
+    {
+        Some(Backend::Il2Cpp)
+    } else if data_dir.join("Managed").is_dir() {
+        Some(Backend::Mono)
+    } else {
+        None
+    }
+}
+
+/// Reads the standalone scripting backend from `ProjectSettings.asset`
+///
+/// ```yaml
+///   scriptingBackend:
+///     Standalone: 1
+/// ```
+fn standalone_backend(settings: &str) -> Backend {
+    let il2cpp = settings
+        .lines()
+        .skip_while(|line| line.trim() != "scriptingBackend:")
+        .skip(1)
+        .take_while(|line| line.starts_with("    "))
+        .any(|line| line.trim() == "Standalone: 1");
+
+    if il2cpp {
+        Backend::Il2Cpp
+    } else {
+        Backend::Mono
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_project(clients_dir: &Path, name: &str, version: &str, settings: &str) {
+        let settings_dir = clients_dir.join(name).join("ProjectSettings");
+        fs::create_dir_all(&settings_dir).unwrap();
+        fs::write(
+            settings_dir.join("ProjectVersion.txt"),
+            format!("m_EditorVersion: {version}\n"),
+        )
+        .unwrap();
+        fs::write(settings_dir.join("ProjectSettings.asset"), settings).unwrap();
+    }
+
+    #[test]
+    fn builtin_games() {
+        let games = registry(None, &BTreeMap::new()).unwrap();
+
+        let latest = games.iter().find(|g| g.name == "unity_latest").unwrap();
+        assert!(latest.supports(&Os::Linux, &Arch::X64));
+        assert_eq!(latest.exe_file(&Os::Windows), "build.exe");
+
+        let base = games.iter().find(|g| g.name == "2022.3.41f1-base").unwrap();
+        assert!(!base.supports(&Os::Linux, &Arch::X64));
+        assert!(base.supports(&Os::Windows, &Arch::X64));
+    }
+
+    #[test]
+    fn discovered_games_are_layered_under_config() {
+        let clients_dir = tempfile::tempdir().unwrap();
+        write_project(
+            clients_dir.path(),
+            "unity_latest",
+            "6000.0.44f1",
+            "  scriptingBackend:\n    Android: 1\n",
+        );
+        write_project(
+            clients_dir.path(),
+            "2019.4-il2cpp",
+            "2019.4.40f1",
+            "  scriptingBackend:\n    Standalone: 1\n  il2cppCompilerConfiguration: {}\n",
+        );
+
+        let config_games = toml::from_str::<RegistryFile>(
+            r#"
+            [games."2019.4-il2cpp"]
+            exe_name = "Game"
+            args = ["-force-glcore"]
+            env = { MONO_ENV_OPTIONS = "--debug" }
+            allowed_log_errors = ["^Shader .* is not supported"]
+            "#,
+        )
+        .unwrap()
+        .games;
+        let games = registry(Some(clients_dir.path()), &config_games).unwrap();
+
+        let latest = games.iter().find(|g| g.name == "unity_latest").unwrap();
+        assert_eq!(latest.unity_version.as_deref(), Some("6000.0.44f1"));
+        assert_eq!(latest.backend, Backend::Mono);
+        assert_eq!(latest.entrypoints, ["general", "old_input_system_movie"]);
+        assert_eq!(latest.benchmarks, ["bench_frame_advance"]);
+
+        let il2cpp = games.iter().find(|g| g.name == "2019.4-il2cpp").unwrap();
+        assert_eq!(il2cpp.backend, Backend::Il2Cpp);
+        assert_eq!(il2cpp.exe_file(&Os::Linux), "Game.x86_64");
+        assert_eq!(il2cpp.entrypoints, [DEFAULT_ENTRYPOINT]);
+        assert_eq!(il2cpp.args, ["-force-glcore"]);
+        assert_eq!(il2cpp.env["MONO_ENV_OPTIONS"], "--debug");
+        assert_eq!(il2cpp.allowed_log_errors, ["^Shader .* is not supported"]);
+        assert!(latest.allowed_log_errors.is_empty());
+    }
+
+    #[test]
+    fn backend_is_detected_from_build_files() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(detect_backend(dir.path(), "build"), None);
+
+        let mono = dir.path().join("mono");
+        fs::create_dir_all(mono.join("build_Data").join("Managed")).unwrap();
+        assert_eq!(detect_backend(&mono, "build"), Some(Backend::Mono));
+
+        let il2cpp = dir.path().join("il2cpp");
+        fs::create_dir_all(il2cpp.join("build_Data").join("il2cpp_data")).unwrap();
+        fs::write(il2cpp.join("GameAssembly.so"), b"").unwrap();
+        assert_eq!(detect_backend(&il2cpp, "build"), Some(Backend::Il2Cpp));
+    }
+}
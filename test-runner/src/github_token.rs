@@ -0,0 +1,284 @@
+use std::{
+    env,
+    fmt::{Debug, Display},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+
+const TOKEN_ENV_VARS: [&str; 2] = ["GITHUB_TOKEN", "GH_TOKEN"];
+const TOKEN_FILE_NAME: &str = "github-token";
+
+/// Where the github token was found
+pub enum TokenSource {
+    Cli,
+    Env(&'static str),
+    GhCli,
+    File(PathBuf),
+}
+
+impl Display for TokenSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenSource::Cli => write!(f, "`--github-token`"),
+            TokenSource::Env(var) => write!(f, "environment variable `{var}`"),
+            TokenSource::GhCli => write!(f, "`gh auth token`"),
+            TokenSource::File(path) => write!(f, "token file `{}`", path.display()),
+        }
+    }
+}
+
+pub struct GithubToken {
+    pub token: String,
+    pub source: TokenSource,
+}
+
+// never print the token itself
+impl Debug for GithubToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GithubToken")
+            .field("token", &"<redacted>")
+            .field("source", &self.source.to_string())
+            .finish()
+    }
+}
+
+/// Finds a github token, trying in order:
+///
+/// - `--github-token`, where an empty token means offline mode and stops looking further
+/// - `GITHUB_TOKEN` or `GH_TOKEN` environment variables
+/// - `gh auth token`, if `gh` is installed and logged in
+/// - token file, either given by `--github-token-file` or `github-token` in the user config directory
+pub fn resolve(
+    cli_token: Option<String>,
+    token_file: Option<&Path>,
+) -> Result<Option<GithubToken>> {
+    resolve_from(
+        cli_token,
+        token_file,
+        |var| env::var(var).ok(),
+        gh_auth_token,
+        default_token_file(),
+    )
+}
+
+fn resolve_from(
+    cli_token: Option<String>,
+    token_file: Option<&Path>,
+    env_var: impl Fn(&str) -> Option<String>,
+    gh_auth_token: impl FnOnce() -> Option<String>,
+    default_token_file: Option<PathBuf>,
+) -> Result<Option<GithubToken>> {
+    let found = |token: String, source| {
+        Some(GithubToken {
+            token: token.trim().to_string(),
+            source,
+        })
+    };
+
+    if let Some(token) = cli_token {
+        if token.trim().is_empty() {
+            return Ok(None);
+        }
+        return Ok(found(token, TokenSource::Cli));
+    }
+
+    for var in TOKEN_ENV_VARS {
+        if let Some(token) = env_var(var).filter(|token| !token.trim().is_empty()) {
+            return Ok(found(token, TokenSource::Env(var)));
+        }
+    }
+
+    // explicitly given file is preferred over gh
+    if let Some(path) = token_file {
+        let token = read_token_file(path)?
+            .with_context(|| format!("token file `{}` doesn't exist", path.display()))?;
+        return Ok(found(token, TokenSource::File(path.to_path_buf())));
+    }
+
+    if let Some(token) = gh_auth_token() {
+        return Ok(found(token, TokenSource::GhCli));
+    }
+
+    if let Some(path) = default_token_file {
+        if let Some(token) = read_token_file(&path)? {
+            return Ok(found(token, TokenSource::File(path)));
+        }
+    }
+
+    Ok(None)
+}
+
+fn gh_auth_token() -> Option<String> {
+    let output = match Command::new("gh").args(["auth", "token"]).output() {
+        Ok(output) => output,
+        Err(err) => {
+            if err.kind() != ErrorKind::NotFound {
+                debug!("failed to run `gh auth token`: {err}");
+            }
+            return None;
+        }
+    };
+
+    if !output.status.success() {
+        debug!("`gh auth token` exited with {}", output.status);
+        return None;
+    }
+
+    String::from_utf8(output.stdout)
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+}
+
+/// `github-token` in the user config directory, like `~/.config/test-runner/github-token`
+fn default_token_file() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        PathBuf::from(dir)
+    } else {
+        PathBuf::from(env::var_os("HOME")?).join(".config")
+    };
+
+    Some(
+        config_dir
+            .join(env!("CARGO_PKG_NAME"))
+            .join(TOKEN_FILE_NAME),
+    )
+}
+
+/// Reads token file, returns `None` if the file doesn't exist
+///
+/// On unix, the file must not be accessible by group or others
+fn read_token_file(path: &Path) -> Result<Option<String>> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("failed to read token file `{}`", path.display()))
+        }
+    };
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = metadata.permissions().mode();
+        if mode & 0o077 != 0 {
+            bail!(
+                "token file `{}` has permissions {:o}, which lets other users read it, restrict it with `chmod 600`",
+                path.display(),
+                mode & 0o777
+            );
+        }
+    }
+    #[cfg(not(target_family = "unix"))]
+    let _ = metadata;
+
+    let token = fs::read_to_string(path)
+        .with_context(|| format!("failed to read token file `{}`", path.display()))?;
+    if token.trim().is_empty() {
+        bail!("token file `{}` is empty", path.display());
+    }
+
+    Ok(Some(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_token(path: &Path, token: &str, mode: u32) {
+        fs::write(path, token).unwrap();
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+        }
+        #[cfg(not(target_family = "unix"))]
+        let _ = mode;
+    }
+
+    /// Source of the token found, with `gh` never logged in
+    fn source_of(
+        cli_token: Option<&str>,
+        env: &[(&str, &str)],
+        token_file: Option<&Path>,
+        default_token_file: &Path,
+    ) -> Option<String> {
+        resolve_from(
+            cli_token.map(str::to_string),
+            token_file,
+            |var| {
+                env.iter()
+                    .find(|(name, _)| *name == var)
+                    .map(|(_, value)| value.to_string())
+            },
+            || None,
+            Some(default_token_file.to_path_buf()),
+        )
+        .unwrap()
+        .map(|found| format!("{}={}", found.source, found.token))
+    }
+
+    #[test]
+    fn resolution_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("token");
+        let default_file = dir.path().join("default-token");
+        write_token(&file, "file\n", 0o600);
+        write_token(&default_file, "default", 0o600);
+        let env = [("GH_TOKEN", "gh"), ("GITHUB_TOKEN", "github")];
+
+        assert_eq!(
+            source_of(Some("cli"), &env, Some(&file), &default_file).unwrap(),
+            "`--github-token`=cli"
+        );
+        assert_eq!(
+            source_of(None, &env, Some(&file), &default_file).unwrap(),
+            "environment variable `GITHUB_TOKEN`=github"
+        );
+        assert_eq!(
+            source_of(None, &[("GITHUB_TOKEN", " ")], Some(&file), &default_file).unwrap(),
+            format!("token file `{}`=file", file.display())
+        );
+        assert_eq!(
+            source_of(None, &[], None, &default_file).unwrap(),
+            format!("token file `{}`=default", default_file.display())
+        );
+        assert_eq!(
+            source_of(None, &[], None, &dir.path().join("missing")),
+            None
+        );
+    }
+
+    #[test]
+    fn empty_cli_token_is_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let default_file = dir.path().join("default-token");
+        write_token(&default_file, "default", 0o600);
+
+        assert_eq!(
+            source_of(Some(""), &[("GITHUB_TOKEN", "github")], None, &default_file),
+            None
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn readable_token_file_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        for mode in [0o640, 0o604] {
+            let file = dir.path().join(format!("token-{mode:o}"));
+            write_token(&file, "token", mode);
+
+            assert!(resolve_from(None, Some(&file), |_| None, || None, None).is_err());
+        }
+    }
+}
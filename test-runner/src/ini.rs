@@ -0,0 +1,219 @@
+//! Editing of ini files as used by BepInEx and Doorstop, keeping comments and layout of the rest of the file
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::Deserialize;
+
+/// Values to set, by section then key
+pub type Settings = BTreeMap<String, BTreeMap<String, Value>>;
+
+/// Setting value, toml values are written like BepInEx writes them
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Value {
+    String(String),
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "{value}"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Int(value) => write!(f, "{value}"),
+            Value::Float(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Layers settings on top of a base file, later values win
+pub struct Builder {
+    contents: String,
+}
+
+impl Builder {
+    pub fn new(base: &str) -> Self {
+        Self {
+            contents: base.to_string(),
+        }
+    }
+
+    pub fn merge(mut self, settings: &Settings) -> Self {
+        for (section, values) in settings {
+            for (key, value) in values {
+                self = self.set(section, key, value);
+            }
+        }
+        self
+    }
+
+    pub fn set(mut self, section: &str, key: &str, value: impl Display) -> Self {
+        self.contents = set_value(&self.contents, section, key, &value.to_string());
+        self
+    }
+
+    pub fn build(self) -> String {
+        self.contents
+    }
+}
+
+/// Sets `key` in `section`, adding the key or the whole section if missing
+///
+/// Section and key names are compared case insensitively, like Doorstop and BepInEx read them
+pub fn set_value(contents: &str, section: &str, key: &str, value: &str) -> String {
+    let mut lines = contents.lines().map(str::to_string).collect::<Vec<_>>();
+    let entry = format!("{key} = {value}");
+
+    let Some(start) = lines
+        .iter()
+        .position(|line| section_name(line).is_some_and(|name| name.eq_ignore_ascii_case(section)))
+    else {
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push(format!("[{section}]"));
+        lines.push(entry);
+        return join(lines);
+    };
+
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| section_name(line).is_some())
+        .map_or(lines.len(), |i| start + 1 + i);
+
+    match (start + 1..end)
+        .find(|&i| key_name(&lines[i]).is_some_and(|name| name.eq_ignore_ascii_case(key)))
+    {
+        // keeps how the file spells the key
+        Some(i) => lines[i] = format!("{} = {value}", key_name(&lines[i]).unwrap()),
+        None => {
+            // after the last non blank line of the section, so the blank line before the next section stays
+            let insert_at = (start + 1..end)
+                .rev()
+                .find(|&i| !lines[i].trim().is_empty())
+                .map_or(start + 1, |i| i + 1);
+            lines.insert(insert_at, entry);
+        }
+    }
+
+    join(lines)
+}
+
+/// Value of `key` in `section`
+pub fn get_value<'a>(contents: &'a str, section: &str, key: &str) -> Option<&'a str> {
+    let mut in_section = false;
+    for line in contents.lines() {
+        if let Some(name) = section_name(line) {
+            in_section = name.eq_ignore_ascii_case(section);
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        if !is_comment(line) && name.trim().eq_ignore_ascii_case(key) {
+            return Some(value.trim());
+        }
+    }
+
+    None
+}
+
+fn section_name(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix('[')?
+        .strip_suffix(']')
+        .map(str::trim)
+}
+
+fn key_name(line: &str) -> Option<&str> {
+    if is_comment(line) {
+        return None;
+    }
+    line.split_once('=').map(|(name, _)| name.trim())
+}
+
+fn is_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with('#') || line.starts_with(';')
+}
+
+fn join(lines: Vec<String>) -> String {
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOORSTOP_CONFIG: &str = "# General options for Unity Doorstop
+[General]
+
+# Enable Doorstop?
+enabled = false
+
+# Path to the assembly to load and execute
+target_assembly = BepInEx\\core\\BepInEx.Preloader.dll
+
+[UnityMono]
+debug_enabled = false
+";
+
+    #[test]
+    fn replaces_value_keeping_comments() {
+        let contents = set_value(DOORSTOP_CONFIG, "general", "Enabled", "true");
+
+        assert_eq!(get_value(&contents, "General", "enabled"), Some("true"));
+        assert!(contents.contains("# Enable Doorstop?\nenabled = true\n"));
+        assert_eq!(contents.lines().count(), DOORSTOP_CONFIG.lines().count());
+        // same result when done again
+        assert_eq!(set_value(&contents, "General", "enabled", "true"), contents);
+    }
+
+    #[test]
+    fn adds_missing_key_and_section() {
+        let contents = set_value(DOORSTOP_CONFIG, "General", "redirect_output_log", "true");
+        let contents = set_value(&contents, "Remote", "Port", "8080");
+
+        assert!(contents.contains(
+            "target_assembly = BepInEx\\core\\BepInEx.Preloader.dll\nredirect_output_log = true\n\n[UnityMono]"
+        ));
+        assert!(contents.ends_with("debug_enabled = false\n\n[Remote]\nPort = 8080\n"));
+    }
+
+    #[test]
+    fn builder_layers_settings() {
+        let settings: Settings = toml::from_str(
+            r#"
+            [General]
+            enabled = true
+            [UnityMono]
+            debug_address = "127.0.0.1:10000"
+            "#,
+        )
+        .unwrap();
+
+        let contents = Builder::new(DOORSTOP_CONFIG)
+            .merge(&settings)
+            .set("UnityMono", "debug_enabled", true)
+            .build();
+
+        assert_eq!(get_value(&contents, "General", "enabled"), Some("true"));
+        assert_eq!(
+            get_value(&contents, "UnityMono", "debug_address"),
+            Some("127.0.0.1:10000")
+        );
+        assert_eq!(
+            get_value(&contents, "UnityMono", "debug_enabled"),
+            Some("true")
+        );
+        assert!(contents.starts_with("# General options for Unity Doorstop\n"));
+    }
+}
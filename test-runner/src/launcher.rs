@@ -0,0 +1,342 @@
+//! Sets up how a game is started with BepInEx loaded through Doorstop
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{ini, Os};
+
+const RUN_SCRIPT: &str = "run_bepinex.sh";
+const DOORSTOP_CONFIG: &str = "doorstop_config.ini";
+const DOORSTOP_LIB: &str = "libdoorstop.so";
+
+/// How the game is started on linux, windows always runs the executable and Doorstop reads `doorstop_config.ini`
+#[derive(ValueEnum, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaunchMode {
+    /// Through `run_bepinex.sh`
+    #[default]
+    Script,
+    /// Executable with the Doorstop environment variables `run_bepinex.sh` would set
+    Direct,
+}
+
+/// Program to run, and environment it needs for BepInEx to load
+pub struct Launch {
+    pub program: PathBuf,
+    pub env: BTreeMap<String, String>,
+}
+
+/// Prepares a game which has BepInEx copied in, can be done any number of times on the same dir
+pub fn prepare(game_dir: &Path, exe_file: &str, os: &Os, mode: LaunchMode) -> Result<Launch> {
+    let exe = game_dir.join(exe_file);
+
+    match os {
+        Os::Windows => {
+            configure_doorstop(game_dir)?;
+            Ok(Launch {
+                program: exe,
+                env: BTreeMap::new(),
+            })
+        }
+        Os::Linux => {
+            let script_path = game_dir.join(RUN_SCRIPT);
+            let script = fs::read_to_string(&script_path).with_context(|| {
+                format!(
+                    "failed to read {RUN_SCRIPT} from `{}`",
+                    script_path.display()
+                )
+            })?;
+
+            let launch = match mode {
+                LaunchMode::Script => {
+                    let patched = patch_run_script(&script, exe_file)?;
+                    if patched != script {
+                        fs::write(&script_path, patched).with_context(|| {
+                            format!("failed to write `{}`", script_path.display())
+                        })?;
+                    }
+                    set_executable(&script_path)?;
+
+                    Launch {
+                        program: script_path,
+                        env: BTreeMap::new(),
+                    }
+                }
+                LaunchMode::Direct => Launch {
+                    program: exe.clone(),
+                    env: doorstop_env(game_dir, &script)?,
+                },
+            };
+            set_executable(&exe)?;
+
+            Ok(launch)
+        }
+    }
+}
+
+/// Adds game and run specific variables to the launch environment, overriding it except for
+/// `LD_PRELOAD` which is appended to, as replacing it would unload Doorstop
+pub fn extend_env(
+    env: &mut BTreeMap<String, String>,
+    vars: impl IntoIterator<Item = (String, String)>,
+) {
+    for (key, value) in vars {
+        match env.get_mut(&key) {
+            Some(preload) if key == "LD_PRELOAD" && !preload.is_empty() => {
+                if !value.is_empty() {
+                    preload.push(':');
+                    preload.push_str(&value);
+                }
+            }
+            _ => {
+                env.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Points `executable_name` of `run_bepinex.sh` to the game executable
+pub fn patch_run_script(script: &str, exe_file: &str) -> Result<String> {
+    let executable_name = Regex::new(r#"(?m)^executable_name=.*$"#).unwrap();
+    if !executable_name.is_match(script) {
+        bail!("failed to find executable_name config in {RUN_SCRIPT}");
+    }
+
+    Ok(executable_name
+        .replace(script, format!(r#"executable_name="{exe_file}""#))
+        .into_owned())
+}
+
+/// Doorstop 4 settings from the variables at the top of `run_bepinex.sh`, as environment variables
+fn doorstop_env(game_dir: &Path, script: &str) -> Result<BTreeMap<String, String>> {
+    let vars = script_vars(script);
+    let var = |name: &str| vars.get(name).map(String::as_str).unwrap_or_default();
+    let path = |value: &str| game_dir.join(value).to_string_lossy().to_string();
+
+    let target_assembly = var("target_assembly");
+    if target_assembly.is_empty() {
+        bail!("{RUN_SCRIPT} has no `target_assembly`, direct launch needs BepInEx with Doorstop 4, use the script launch mode instead");
+    }
+    let doorstop_lib = game_dir.join(DOORSTOP_LIB);
+    if !doorstop_lib.is_file() {
+        bail!(
+            "failed to find Doorstop library `{}`",
+            doorstop_lib.display()
+        );
+    }
+
+    let mut env = BTreeMap::new();
+    let mut set = |key: &str, value: String| {
+        env.insert(key.to_string(), value);
+    };
+
+    set("DOORSTOP_ENABLED", "1".to_string());
+    set("DOORSTOP_TARGET_ASSEMBLY", path(target_assembly));
+    set("DOORSTOP_IGNORE_DISABLED_ENV", "0".to_string());
+    set("DOORSTOP_MONO_DEBUG_ENABLED", "0".to_string());
+    for (script_var, env_var) in [
+        ("boot_config_override", "DOORSTOP_BOOT_CONFIG_OVERRIDE"),
+        (
+            "mono_dll_search_path_override",
+            "DOORSTOP_MONO_DLL_SEARCH_PATH_OVERRIDE",
+        ),
+        ("coreclr_path", "DOORSTOP_CLR_RUNTIME_CORECLR_PATH"),
+        ("corlib_dir", "DOORSTOP_CLR_CORLIB_DIR"),
+    ] {
+        let value = var(script_var);
+        if !value.is_empty() {
+            set(env_var, path(value));
+        }
+    }
+
+    let mut library_path = vec![game_dir.to_string_lossy().to_string()];
+    if !var("corlib_dir").is_empty() {
+        library_path.push(path(var("corlib_dir")));
+    }
+    if let Ok(existing) = env::var("LD_LIBRARY_PATH") {
+        library_path.push(existing);
+    }
+    set("LD_LIBRARY_PATH", library_path.join(":"));
+    // prepended like `run_bepinex.sh` does, so preloads of the environment still load
+    let mut preload = vec![doorstop_lib.to_string_lossy().to_string()];
+    preload.extend(
+        env::var("LD_PRELOAD")
+            .ok()
+            .filter(|existing| !existing.is_empty()),
+    );
+    set("LD_PRELOAD", preload.join(":"));
+
+    Ok(env)
+}
+
+/// Top level `name=value` assignments, with quotes removed
+fn script_vars(script: &str) -> BTreeMap<String, String> {
+    let assignment = Regex::new(r#"^([A-Za-z_][A-Za-z0-9_]*)=(.*)$"#).unwrap();
+
+    script
+        .lines()
+        .filter_map(|line| assignment.captures(line))
+        .map(|captures| {
+            let value = captures[2].trim();
+            let value = ["\"", "'"]
+                .iter()
+                .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+                .unwrap_or(value);
+            (captures[1].to_string(), value.to_string())
+        })
+        .collect()
+}
+
+/// Makes sure Doorstop is enabled and its target assembly exists
+fn configure_doorstop(game_dir: &Path) -> Result<()> {
+    let config_path = game_dir.join(DOORSTOP_CONFIG);
+    let config = fs::read_to_string(&config_path).with_context(|| {
+        format!(
+            "failed to read Doorstop config from `{}`",
+            config_path.display()
+        )
+    })?;
+
+    // Doorstop 4 uses [General], Doorstop 3 [UnityDoorstop]
+    let (section, target_key) = if ini::get_value(&config, "General", "enabled").is_some() {
+        ("General", "target_assembly")
+    } else {
+        ("UnityDoorstop", "targetAssembly")
+    };
+
+    let target_assembly = ini::get_value(&config, section, target_key)
+        .with_context(|| format!("`{}` has no `{target_key}`", config_path.display()))?;
+    let target_path = game_dir.join(target_assembly.replace('\\', "/"));
+    if !target_path.is_file() {
+        bail!(
+            "Doorstop target assembly `{}` doesn't exist",
+            target_path.display()
+        );
+    }
+
+    let patched = ini::set_value(&config, section, "enabled", "true");
+    if patched != config {
+        fs::write(&config_path, patched)
+            .with_context(|| format!("failed to write `{}`", config_path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn set_executable(path: &Path) -> Result<()> {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(0o744)).with_context(|| {
+            format!("failed to set execute permissions for `{}`", path.display())
+        })?;
+    }
+    #[cfg(not(target_family = "unix"))]
+    let _ = path;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUN_SCRIPT_CONTENTS: &str = r#"#!/bin/sh
+# BepInEx start script
+executable_name=""
+
+# Enable Doorstop?
+enabled="1"
+target_assembly="BepInEx/core/BepInEx.Preloader.dll"
+coreclr_path=''
+corlib_dir=
+"#;
+
+    #[test]
+    fn run_script_patch_is_idempotent() {
+        let once = patch_run_script(RUN_SCRIPT_CONTENTS, "build.x86_64").unwrap();
+        let twice = patch_run_script(&once, "build.x86_64").unwrap();
+
+        assert!(once.contains("\nexecutable_name=\"build.x86_64\"\n"));
+        assert_eq!(once, twice);
+        assert!(patch_run_script("#!/bin/sh\n", "build.x86_64").is_err());
+    }
+
+    #[test]
+    fn direct_launch_env_from_script() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(DOORSTOP_LIB), b"").unwrap();
+
+        let env = doorstop_env(dir.path(), RUN_SCRIPT_CONTENTS).unwrap();
+
+        assert_eq!(env["DOORSTOP_ENABLED"], "1");
+        assert_eq!(
+            Path::new(&env["DOORSTOP_TARGET_ASSEMBLY"]),
+            dir.path().join("BepInEx/core/BepInEx.Preloader.dll")
+        );
+        assert!(!env.contains_key("DOORSTOP_CLR_CORLIB_DIR"));
+        assert!(env["LD_PRELOAD"]
+            .split(':')
+            .next()
+            .unwrap()
+            .ends_with(DOORSTOP_LIB));
+    }
+
+    #[test]
+    fn preload_is_appended_to() {
+        let mut env = BTreeMap::from([
+            ("LD_PRELOAD".to_string(), "libdoorstop.so".to_string()),
+            ("MONO_ENV_OPTIONS".to_string(), "--debug".to_string()),
+        ]);
+
+        extend_env(
+            &mut env,
+            [
+                ("LD_PRELOAD".to_string(), "libasan.so".to_string()),
+                ("MONO_ENV_OPTIONS".to_string(), "".to_string()),
+            ],
+        );
+        extend_env(
+            &mut env,
+            [("LD_PRELOAD".to_string(), "libtrace.so".to_string())],
+        );
+
+        assert_eq!(env["LD_PRELOAD"], "libdoorstop.so:libasan.so:libtrace.so");
+        assert_eq!(env["MONO_ENV_OPTIONS"], "");
+
+        // script launches leave preloading to `run_bepinex.sh`
+        let mut env = BTreeMap::new();
+        extend_env(
+            &mut env,
+            [("LD_PRELOAD".to_string(), "libasan.so".to_string())],
+        );
+        assert_eq!(env["LD_PRELOAD"], "libasan.so");
+    }
+
+    #[test]
+    fn doorstop_config_is_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = dir.path().join("BepInEx").join("core");
+        fs::create_dir_all(&core).unwrap();
+        fs::write(core.join("BepInEx.Preloader.dll"), b"").unwrap();
+        fs::write(
+            dir.path().join(DOORSTOP_CONFIG),
+            "[General]\nenabled = false\ntarget_assembly = BepInEx\\core\\BepInEx.Preloader.dll\n",
+        )
+        .unwrap();
+
+        configure_doorstop(dir.path()).unwrap();
+
+        let config = fs::read_to_string(dir.path().join(DOORSTOP_CONFIG)).unwrap();
+        assert_eq!(ini::get_value(&config, "General", "enabled"), Some("true"));
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::games::Backend;
+
+const LOCKFILE_VERSION: u32 = 1;
+
+const LOCKFILE_HEADER: &str =
+    "# This file is generated by test-runner, use `test-runner update` to refresh it\n";
+
+/// Exact artifacts used for a test run, so a run can be reproduced with `--locked`
+#[derive(Serialize, Deserialize, Default)]
+pub struct Lockfile {
+    pub version: u32,
+    pub unitas: Option<LockedArtifact>,
+    #[serde(default)]
+    pub bepinex: Vec<LockedRelease>,
+    #[serde(default)]
+    pub games: Vec<LockedArtifact>,
+}
+
+/// Artifact uploaded from a github actions workflow run
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LockedArtifact {
+    pub name: String,
+    pub owner: String,
+    pub repo: String,
+    pub workflow: String,
+    pub run_id: u64,
+    pub artifact_id: u64,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// BepInEx asset from a github release, or a direct download
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LockedRelease {
+    /// Release tag, or the file name for direct downloads
+    pub tag: String,
+    pub asset: String,
+    pub url: String,
+    pub size: u64,
+    pub sha256: String,
+    /// Flavour of BepInEx, for games with that scripting backend
+    #[serde(default)]
+    pub backend: Backend,
+}
+
+impl Lockfile {
+    pub fn new() -> Self {
+        Self {
+            version: LOCKFILE_VERSION,
+            ..Default::default()
+        }
+    }
+
+    pub async fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("failed to read lockfile at `{}`", path.display()))?;
+        let lockfile: Self = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse lockfile at `{}`", path.display()))?;
+
+        if lockfile.version != LOCKFILE_VERSION {
+            bail!(
+                "lockfile at `{}` has version {}, but only version {LOCKFILE_VERSION} is supported, run `test-runner update` to regenerate it",
+                path.display(),
+                lockfile.version
+            );
+        }
+
+        Ok(lockfile)
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let contents = toml::to_string_pretty(self).context("failed to serialize lockfile")?;
+        let contents = format!("{LOCKFILE_HEADER}{contents}");
+
+        fs::write(path, contents)
+            .await
+            .with_context(|| format!("failed to write lockfile to `{}`", path.display()))
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+pub fn verify_sha256(bytes: &[u8], expected: &str, what: &str) -> Result<()> {
+    let actual = sha256_hex(bytes);
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("sha256 mismatch for {what}, expected `{expected}` but downloaded `{actual}`");
+    }
+
+    Ok(())
+}
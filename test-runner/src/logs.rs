@@ -0,0 +1,178 @@
+//! Log directory of each run, laid out as `logs/<timestamp>-<run id>/<game>/`
+
+use std::{
+    fs,
+    hash::{BuildHasher, RandomState},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use colored::Colorize;
+use log::debug;
+use regex::Regex;
+
+use crate::symbols;
+
+/// Timestamps in log directory and file names
+pub const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const LATEST_LINK: &str = "latest";
+
+/// Which old runs are kept, runs matching either are kept
+#[derive(Clone, Copy, Default)]
+pub struct Retention {
+    /// Newest runs to keep, counting the current one
+    pub runs: Option<usize>,
+    pub days: Option<u64>,
+}
+
+/// Creates the directory for this run in `root` and points `latest` to it
+pub fn create_run_dir(root: &Path) -> Result<PathBuf> {
+    let run_id = RandomState::new().hash_one(std::process::id()) as u32;
+    let name = format!("{}-{run_id:08x}", Local::now().format(TIMESTAMP_FORMAT));
+    let dir = root.join(&name);
+
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("failed to create log directory `{}`", dir.display()))?;
+
+    if let Err(err) = link_latest(root, &name) {
+        eprintln!(
+            "{} failed to link `{}` to the logs of this run: {err}",
+            symbols::WARN.yellow(),
+            root.join(LATEST_LINK).display()
+        );
+    }
+
+    Ok(dir)
+}
+
+fn link_latest(root: &Path, name: &str) -> std::io::Result<()> {
+    let link = root.join(LATEST_LINK);
+    match fs::symlink_metadata(&link) {
+        // directory symlinks are directories to windows, but files everywhere else
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            #[cfg(target_family = "windows")]
+            fs::remove_dir(&link)?;
+            #[cfg(not(target_family = "windows"))]
+            fs::remove_file(&link)?;
+        }
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir(&link)?,
+        Ok(_) => fs::remove_file(&link)?,
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+
+    #[cfg(target_family = "unix")]
+    return std::os::unix::fs::symlink(name, &link);
+    #[cfg(target_family = "windows")]
+    return std::os::windows::fs::symlink_dir(name, &link);
+}
+
+/// Removes runs in `root` which are neither in the newest `retention.runs`, nor newer than `retention.days`
+///
+/// Nothing is removed if neither is set
+pub fn apply_retention(root: &Path, retention: Retention) -> Result<()> {
+    if retention.runs.is_none() && retention.days.is_none() {
+        return Ok(());
+    }
+
+    let run_dir_name = Regex::new(r"^(\d{8}-\d{6})-[0-9a-f]{8}$").unwrap();
+    let mut runs = Vec::new();
+    for entry in fs::read_dir(root)
+        .with_context(|| format!("failed to read log directory `{}`", root.display()))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(captures) = run_dir_name.captures(&name) else {
+            continue;
+        };
+        let Ok(time) = NaiveDateTime::parse_from_str(&captures[1], TIMESTAMP_FORMAT) else {
+            continue;
+        };
+        if entry.file_type()?.is_dir() {
+            runs.push((time, entry.path()));
+        }
+    }
+
+    // newest first
+    runs.sort_by(|a, b| b.cmp(a));
+
+    let cutoff = retention
+        .days
+        .map(|days| Local::now().naive_local() - TimeDelta::days(days as i64));
+    for (i, (time, dir)) in runs.into_iter().enumerate() {
+        let keep_by_count = retention.runs.is_some_and(|runs| i < runs);
+        let keep_by_age = cutoff.is_some_and(|cutoff| time >= cutoff);
+        if keep_by_count || keep_by_age {
+            continue;
+        }
+
+        debug!("removing old run logs `{}`", dir.display());
+        fs::remove_dir_all(&dir)
+            .with_context(|| format!("failed to remove old run logs `{}`", dir.display()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_runs_are_removed() {
+        let root = tempfile::tempdir().unwrap();
+        let old = Local::now() - TimeDelta::days(10);
+        for time in [old - TimeDelta::days(1), old] {
+            let name = format!("{}-0000000a", time.format(TIMESTAMP_FORMAT));
+            fs::create_dir(root.path().join(name)).unwrap();
+        }
+        fs::write(root.path().join("unity_latest.log"), b"").unwrap();
+        let current = create_run_dir(root.path()).unwrap();
+
+        apply_retention(
+            root.path(),
+            Retention {
+                runs: Some(2),
+                days: Some(5),
+            },
+        )
+        .unwrap();
+
+        let mut names = fs::read_dir(root.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        let old_name = format!("{}-0000000a", old.format(TIMESTAMP_FORMAT));
+        let current_name = current.file_name().unwrap().to_string_lossy().to_string();
+        assert_eq!(
+            names,
+            [
+                old_name,
+                current_name.clone(),
+                LATEST_LINK.to_string(),
+                "unity_latest.log".to_string()
+            ]
+        );
+        assert_eq!(
+            fs::canonicalize(root.path().join(LATEST_LINK)).unwrap(),
+            fs::canonicalize(current).unwrap()
+        );
+    }
+
+    #[test]
+    fn latest_link_is_replaced() {
+        let root = tempfile::tempdir().unwrap();
+        for name in ["first", "second"] {
+            fs::create_dir(root.path().join(name)).unwrap();
+            link_latest(root.path(), name).unwrap();
+        }
+
+        assert_eq!(
+            fs::canonicalize(root.path().join(LATEST_LINK)).unwrap(),
+            fs::canonicalize(root.path().join("second")).unwrap()
+        );
+    }
+}
@@ -1,33 +1,51 @@
-#[cfg(target_family = "unix")]
-use std::os::unix::fs::PermissionsExt;
 use std::{
+    collections::BTreeMap,
     env::{self, current_exe},
     fmt::Display,
-    path::Path,
+    path::{Path, PathBuf},
     process::ExitCode,
+    sync::Arc,
 };
 
 use anyhow::{bail, Context, Result};
-use clap::Parser;
-use cli::Args;
-use const_format::formatcp;
-use download::{dl_bepinex, dl_test_games, dl_unitas};
+use bench::{Baseline, BenchResult};
+use clap::{CommandFactory, FromArgMatches};
+use cli::{Args, BenchArgs, BepInExVersion, Command};
+use config::Config;
+use display::X11Display;
+use download::{dl_bepinex, dl_test_games, dl_unitas, BepInExRelease, SourceKind};
 use fs_utils::copy_dir_all;
+use games::Backend;
+use github_token::GithubToken;
 use indicatif::MultiProgress;
-use tokio::{
-    fs,
-    task::{self, JoinSet},
-};
-use unitas_tests::{get_linux_tests, get_win_tests};
-
+use lockfile::{LockedRelease, Lockfile};
+use logs::Retention;
+use report::{GameReport, Report};
+use serde::Deserialize;
+use tokio::{fs, task};
+use unitas_tests::Test;
+
+mod bench;
+mod cfg_files;
 mod cli;
+mod config;
+mod display;
 mod download;
 mod fs_utils;
+mod game_logs;
+mod games;
+mod github_token;
+mod ini;
+mod launcher;
+mod lockfile;
+mod logs;
 mod movies;
+mod report;
 mod symbols;
 mod unitas_tests;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum Os {
     Linux,
     Windows,
@@ -46,7 +64,8 @@ impl Display for Os {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum Arch {
     X64,
     X86,
@@ -72,12 +91,8 @@ async fn main() -> Result<ExitCode> {
     // dirs in executable dir is all unity games for testing
     let current_exe = current_exe().context("failed to get current exe dir")?;
     let current_dir = current_exe.parent().unwrap();
-    let bepinex_dir = current_dir.join("BepInEx");
     let unitas_dir = current_dir.join("UniTAS");
 
-    let args = Args::parse();
-    args.validate()?;
-
     // os & arch
     let os = match env::consts::OS {
         "linux" => Os::Linux,
@@ -90,172 +105,612 @@ async fn main() -> Result<ExitCode> {
         _ => bail!("unsupported architecture for testing"),
     };
 
-    let tests = match os {
-        Os::Linux => get_linux_tests(),
-        Os::Windows => get_win_tests(),
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    let config_path = if args.no_config {
+        None
+    } else {
+        args.config
+            .clone()
+            .or_else(|| Config::discover(current_dir))
+    };
+    let config = match config_path {
+        Some(path) => {
+            println!("using config `{}`", path.display());
+            Config::load(&path)?
+        }
+        None => Config::default(),
+    };
+    config.apply(&mut args, &matches, &os)?;
+    let github_token = github_token::resolve(
+        args.github_token.to_owned(),
+        args.github_token_file.as_deref(),
+    )?;
+    args.validate(github_token.is_some())?;
+    if args.headed && os != Os::Linux {
+        bail!("`--headed` is only supported on linux");
+    }
+    let github_token = match github_token {
+        Some(GithubToken { token, source }) => {
+            println!("using github token from {source}");
+            Some(token)
+        }
+        None => {
+            println!("no github token found, running in offline mode");
+            None
+        }
+    };
+
+    let update_lockfile = args.command == Some(Command::Update);
+    let lockfile = if args.locked {
+        Lockfile::load(&args.lockfile).await?
+    } else {
+        Lockfile::new()
     };
+    let Lockfile {
+        unitas: locked_unitas,
+        bepinex: locked_bepinex,
+        games: locked_games,
+        ..
+    } = lockfile;
+    let locked_games = args.locked.then_some(locked_games);
+    let unitas_from_actions = args
+        .unitas_source
+        .as_ref()
+        .is_none_or(|source| matches!(source, SourceKind::GithubActions));
+    if args.locked && locked_unitas.is_none() && unitas_from_actions {
+        bail!(
+            "lockfile `{}` has no UniTAS build, run `test-runner update` to refresh it",
+            args.lockfile.display()
+        );
+    }
+
+    let clients_dir = config.clients_dir().or_else(games::find_clients_dir);
+    let tests = games::registry(clients_dir.as_deref(), config.games())?
+        .into_iter()
+        .filter(|game| game.supports(&os, &arch))
+        .map(Test::new)
+        .collect::<Result<Vec<_>>>()?;
+    let mut tests = select_tests(tests, &args.test)?;
 
     // start download and setup tasks
     let pb = MultiProgress::new();
 
-    let dl_bepinex_task = {
-        let bepinex_dir = bepinex_dir.clone();
+    let backends = test_backends(&tests);
+    if args.bepinex_path.is_some() && backends.len() > 1 {
+        bail!("`--bepinex-path` gives a single BepInEx install, which can't be used for both Mono and IL2CPP games");
+    }
+    let mut bepinex_setups = plan_bepinex_setups(current_dir, &args, &locked_bepinex, &backends)?;
+    let endpoints = args.endpoints();
+    let client = download::http_client(&args.http_options())?;
+    let bepinex_source = Arc::new(download::bepinex_source(
+        &args.bepinex_source,
+        &endpoints,
+        &client,
+    )?);
+    let spawn_dl_bepinex = |setup: &BepInExSetup| {
+        let bepinex_dir = setup.dir.clone();
         let arch = arch.clone();
         let pb = pb.clone();
         let path = args.bepinex_path.clone();
         let os = os.clone();
+        let backend = setup.backend;
+        let release = setup.release.clone();
+        let source = bepinex_source.clone();
         task::spawn(async move {
-            dl_bepinex(&bepinex_dir, &os, &arch, pb, path).await;
+            if let Some(path) = path {
+                copy_dir_all(&path, &bepinex_dir)
+                    .await
+                    .context("failed to copy BepInEx directory")?;
+                return Ok(None);
+            }
+
+            dl_bepinex(
+                &bepinex_dir,
+                &os,
+                &arch,
+                backend,
+                pb,
+                release,
+                source,
+                args.symlinks,
+            )
+            .await
         })
     };
+    let mut dl_bepinex_tasks = bepinex_setups
+        .iter()
+        .map(spawn_dl_bepinex)
+        .collect::<Vec<_>>();
     let dl_unitas_task = {
         let unitas_dir = unitas_dir.clone();
         let pb = pb.clone();
-        let token = args.github_token.to_owned();
-        task::spawn(async move { dl_unitas(&unitas_dir, args.download_unitas, pb, token).await })
+        let download_unitas = args.download_unitas
+            || update_lockfile
+            || args.selects_unitas_build()
+            || args.unitas_source.is_some();
+        let source = download::unitas_source(
+            args.unitas_source
+                .as_ref()
+                .unwrap_or(&SourceKind::GithubActions),
+            &endpoints,
+            &client,
+            config.unitas_workflow(&args),
+            github_token.to_owned(),
+        );
+        task::spawn(async move {
+            dl_unitas(
+                &unitas_dir,
+                download_unitas,
+                source,
+                pb,
+                locked_unitas,
+                args.symlinks,
+            )
+            .await
+        })
     };
 
     let dl_games_task = {
         let current_dir = current_dir.to_path_buf();
-        let token = args.github_token.to_owned();
+        let pb = pb.clone();
+        let source = download::games_source(
+            &args.games_source,
+            &endpoints,
+            &client,
+            config.test_games_workflow(),
+            github_token.to_owned(),
+        )?;
+        let names = tests.iter().map(|test| test.name().to_string()).collect();
         let replace_games = args.replace_game.to_owned();
-        task::spawn(async move { dl_test_games(&current_dir, pb, token, replace_games).await })
+        task::spawn(async move {
+            dl_test_games(
+                &current_dir,
+                pb,
+                source,
+                names,
+                replace_games,
+                locked_games,
+                args.symlinks,
+            )
+            .await
+        })
     };
 
-    // wait for bepinex download
-    dl_bepinex_task.await.unwrap();
-
-    let mut post_bepinex_dl_tasks = JoinSet::new();
+    let games = dl_games_task
+        .await
+        .context("test games download task failed")??;
 
-    {
-        let bepinex_dir = bepinex_dir.clone();
-        post_bepinex_dl_tasks
-            .spawn(async move { setup_unitas_config(&bepinex_dir, args.port).await });
+    // the registry can be wrong about downloaded builds, which may need another BepInEx flavour
+    for test in &mut tests {
+        test.detect_backend(current_dir);
+    }
+    let missing_backends = test_backends(&tests)
+        .into_iter()
+        .filter(|backend| !backends.contains(backend))
+        .collect::<Vec<_>>();
+    if !missing_backends.is_empty() {
+        if args.bepinex_path.is_some() {
+            bail!("`--bepinex-path` gives a single BepInEx install, which can't be used for both Mono and IL2CPP games");
+        }
+
+        let setups = plan_bepinex_setups(current_dir, &args, &locked_bepinex, &missing_backends)?;
+        dl_bepinex_tasks.extend(setups.iter().map(spawn_dl_bepinex));
+        bepinex_setups.extend(setups);
     }
 
-    {
-        let bepinex_dir = bepinex_dir.clone();
-        post_bepinex_dl_tasks.spawn(async move { setup_bepinex(&bepinex_dir).await });
+    // wait for bepinex download
+    let mut bepinex = Vec::new();
+    for task in dl_bepinex_tasks {
+        if let Some(locked) = task.await.context("BepInEx download task failed")?? {
+            bepinex.push(locked);
+        }
     }
 
-    // for all UniTAS logs
-    let logs_dir = current_dir.join("logs");
-    fs::create_dir_all(&logs_dir)
+    // wait for unitas and bepinex dl
+    let unitas = dl_unitas_task
         .await
-        .context("failed to create folder for logs")?;
+        .context("UniTAS download task failed")??;
+    for setup in &bepinex_setups {
+        setup_unitas(&unitas_dir, &setup.dir).await?;
+    }
 
-    // wait for unitas and bepinex dl
-    dl_unitas_task.await.unwrap()?;
-    setup_unitas(&unitas_dir, &bepinex_dir).await?;
+    if update_lockfile {
+        let lockfile = Lockfile {
+            unitas,
+            bepinex,
+            games,
+            ..Lockfile::new()
+        };
+        lockfile.save(&args.lockfile).await?;
+        println!("updated lockfile at `{}`", args.lockfile.display());
+
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    dl_games_task.await.unwrap()?;
+    // stays alive until the tests are done
+    let display = if args.headed {
+        let display = if args.use_display {
+            X11Display::existing()?
+        } else {
+            X11Display::start_xvfb(args.resolution)?
+        };
+        println!(
+            "running games headed on display `{}` at {}",
+            display.name(),
+            args.resolution
+        );
+        Some(display)
+    } else {
+        None
+    };
 
-    while post_bepinex_dl_tasks.join_next().await.is_some() {}
+    // for all UniTAS logs
+    let logs_root = args
+        .logs_dir
+        .clone()
+        .unwrap_or_else(|| current_dir.join("logs"));
+    let logs_dir = logs::create_run_dir(&logs_root)?;
+    logs::apply_retention(
+        &logs_root,
+        Retention {
+            runs: args.keep_runs,
+            days: args.keep_days,
+        },
+    )?;
+    println!("writing logs to `{}`", logs_dir.display());
 
     // run
-    for test in tests {
-        test.run(current_dir, &bepinex_dir, &logs_dir, &os, &args)?;
+    let mut report = Report::default();
+    let result = match &args.command {
+        Some(Command::Bench(bench_args)) => {
+            run_bench(
+                current_dir,
+                &bepinex_setups,
+                &tests,
+                &logs_dir,
+                &os,
+                &args,
+                bench_args,
+                display.as_ref(),
+                &mut report,
+            )
+            .await
+        }
+        _ => {
+            run_tests(
+                current_dir,
+                &bepinex_setups,
+                &tests,
+                &logs_dir,
+                &os,
+                &args,
+                display.as_ref(),
+                &mut report,
+            )
+            .await
+        }
+    };
+
+    if let Some(path) = &args.report_json {
+        report.save_json(path).await?;
+        println!("wrote report to `{}`", path.display());
     }
 
+    result?;
+
     Ok(ExitCode::SUCCESS)
 }
 
-async fn setup_unitas(unitas_dir: &Path, bepinex_dir: &Path) -> Result<()> {
-    copy_dir_all(unitas_dir, bepinex_dir)
-        .await
-        .with_context(|| {
-            format!(
-                "failed to copy UniTAS dir contents from `{}` to game folder `{}`",
-                unitas_dir.display(),
-                bepinex_dir.display()
-            )
-        })
-}
+#[allow(clippy::too_many_arguments)]
+async fn run_tests(
+    current_dir: &Path,
+    bepinex_setups: &[BepInExSetup],
+    tests: &[Test],
+    logs_dir: &Path,
+    os: &Os,
+    args: &Args,
+    display: Option<&X11Display>,
+    report: &mut Report,
+) -> Result<()> {
+    // games run in place if no backend is tested with more than one BepInEx version
+    let single = bepinex_setups.iter().all(|setup| {
+        bepinex_setups
+            .iter()
+            .filter(|other| other.backend == setup.backend)
+            .count()
+            == 1
+    });
+    for setup in bepinex_setups {
+        let tests = tests
+            .iter()
+            .filter(|test| test.backend() == setup.backend)
+            .collect::<Vec<_>>();
+        if tests.is_empty() {
+            continue;
+        }
+
+        let (games_dir, logs_dir) = if single {
+            (current_dir.to_path_buf(), logs_dir.to_path_buf())
+        } else {
+            println!("running tests with BepInEx {}\n", setup.label);
+
+            // each BepInEx version gets fresh copies of the games, so installs don't mix
+            let games_dir = current_dir.join("runs").join(&setup.dir_name);
+            let logs_dir = logs_dir.join(&setup.dir_name);
+            prepare_games_dir(current_dir, &games_dir, &tests).await?;
+            fs::create_dir_all(&logs_dir)
+                .await
+                .context("failed to create folder for logs")?;
+
+            (games_dir, logs_dir)
+        };
+
+        for test in tests {
+            let logs_dir = logs_dir.join(test.name());
+            fs::create_dir_all(&logs_dir)
+                .await
+                .context("failed to create folder for logs")?;
+
+            let mut game_report = GameReport::new(test.name(), &setup.label);
+            game_report.logs_dir = Some(logs_dir.clone());
+            let result = test.run(
+                &games_dir,
+                &setup.dir,
+                &logs_dir,
+                os,
+                args,
+                display,
+                &mut game_report,
+            );
+            if let Err(err) = &result {
+                game_report.error = Some(err.to_string());
+            }
+            report.games.push(game_report);
+
+            result?;
+        }
+    }
 
-const GAME_BIN_NAME: &str = "build";
-const WIN_UNITY_EXE_NAME: &str = formatcp!("{GAME_BIN_NAME}.exe");
-const UNIX_UNITY_EXE_NAME: &str = formatcp!("{GAME_BIN_NAME}.x86_64");
+    Ok(())
+}
 
-async fn setup_bepinex(bepinex_dir: &Path) -> Result<()> {
-    #[cfg(target_family = "unix")]
-    {
-        let run_bepinex_file = bepinex_dir.join("run_bepinex.sh");
+/// Plays the benchmarks of the games and compares the results against the baseline
+#[allow(clippy::too_many_arguments)]
+async fn run_bench(
+    current_dir: &Path,
+    bepinex_setups: &[BepInExSetup],
+    tests: &[Test],
+    logs_dir: &Path,
+    os: &Os,
+    args: &Args,
+    bench_args: &BenchArgs,
+    display: Option<&X11Display>,
+    report: &mut Report,
+) -> Result<()> {
+    if bepinex_setups.iter().any(|setup| {
+        bepinex_setups
+            .iter()
+            .filter(|other| other.backend == setup.backend)
+            .count()
+            > 1
+    }) {
+        bail!("benchmarks run with one BepInEx version per backend, select one with `--bepinex-version`");
+    }
 
-        // modify run_bepinex to execute correct executable
-        let mut run_bepinex_content =
-            fs::read_to_string(&run_bepinex_file)
+    let mut results = BTreeMap::new();
+    for setup in bepinex_setups {
+        let tests = tests
+            .iter()
+            .filter(|test| test.backend() == setup.backend && test.has_benchmarks());
+        for test in tests {
+            let logs_dir = logs_dir.join(test.name());
+            fs::create_dir_all(&logs_dir)
                 .await
-                .with_context(|| {
-                    format!(
-                        "failed to open run_bepinex.sh from `{}`",
-                        run_bepinex_file.display()
-                    )
-                })?;
+                .context("failed to create folder for logs")?;
+
+            let mut game_report = GameReport::new(test.name(), &setup.label);
+            game_report.logs_dir = Some(logs_dir.clone());
+            let samples = test.bench(
+                current_dir,
+                &setup.dir,
+                &logs_dir,
+                os,
+                args,
+                display,
+                bench_args.runs,
+                &mut game_report,
+            );
+            if let Err(err) = &samples {
+                game_report.error = Some(err.to_string());
+            }
+            report.games.push(game_report);
+
+            for (name, runs) in samples? {
+                let name = format!("{}/{name}", test.name());
+                let result = BenchResult::new(&runs)
+                    .with_context(|| format!("invalid results of benchmark `{name}`"))?;
+                results.insert(name, result);
+            }
+        }
+    }
+    if results.is_empty() {
+        bail!("none of the selected games have benchmarks");
+    }
 
-        let find_key = "executable_name=";
-        let find_index = run_bepinex_content
-            .find(find_key)
-            .context("failed to find executable_name config in run_bepinex.sh")?;
+    let mut baseline = Baseline::load(&bench_args.baseline).await?;
+    let regressions = bench::print_comparison(&results, &baseline, bench_args);
 
-        run_bepinex_content.insert_str(find_index + find_key.len() + 1, UNIX_UNITY_EXE_NAME);
+    let results_path = logs_dir.join("bench.json");
+    Baseline {
+        benchmarks: results.clone(),
+    }
+    .save(&results_path)
+    .await?;
+    println!("wrote benchmark results to `{}`", results_path.display());
+
+    if bench_args.save_baseline {
+        baseline.benchmarks.extend(results);
+        baseline.save(&bench_args.baseline).await?;
+        println!("saved baseline to `{}`", bench_args.baseline.display());
+    }
 
-        fs::write(&run_bepinex_file, run_bepinex_content)
-            .await
-            .with_context(|| {
-                format!(
-                    "failed to write run_bepinex.sh content to `{}`",
-                    run_bepinex_file.display()
-                )
-            })?;
-
-        // set perms for execution
-        let mut perms = run_bepinex_file
-            .metadata()
-            .with_context(|| {
-                format!(
-                    "failed to get run_bepinex.sh metadata from path `{}`",
-                    run_bepinex_file.display()
-                )
-            })?
-            .permissions();
-
-        perms.set_mode(0o744);
-
-        fs::set_permissions(&run_bepinex_file, perms)
-            .await
-            .with_context(|| {
-                format!(
-                    "failed to set execute permissions for run_bepinex.sh at path `{}`",
-                    run_bepinex_file.display()
-                )
-            })?;
+    if !regressions.is_empty() {
+        bail!("benchmarks regressed: {}", regressions.join(", "));
     }
 
     Ok(())
 }
 
-async fn setup_unitas_config(bepinex_dir: &Path, port: u16) -> Result<()> {
-    let cfg = bepinex_dir.join("BepInEx").join("config");
+/// Tests of the given game names, or all tests if no names are given
+fn select_tests(tests: Vec<Test>, names: &[String]) -> Result<Vec<Test>> {
+    if names.is_empty() {
+        return Ok(tests);
+    }
 
-    fs::create_dir_all(&cfg).await.with_context(|| {
-        format!(
-            "failed to create directory for UniTAS config file at path `{}`",
-            cfg.display()
-        )
-    })?;
-
-    let cfg = cfg.join("UniTAS.cfg");
-    let contents = format!(
-        r#"[Remote]
-Enable = true
-Port = {port}
-"#
-    );
-
-    fs::write(&cfg, contents).await.with_context(|| {
-        format!(
-            "failed to write config for UniTAS to path `{}`",
-            cfg.display()
-        )
-    })
+    for name in names {
+        if !tests.iter().any(|test| test.name() == name) {
+            let available = tests
+                .iter()
+                .map(|test| test.name())
+                .collect::<Vec<_>>()
+                .join(", ");
+            bail!("unknown test game `{name}`, available games: [{available}]");
+        }
+    }
+
+    Ok(tests
+        .into_iter()
+        .filter(|test| names.iter().any(|name| name == test.name()))
+        .collect())
+}
+
+/// BepInEx install which the test suite runs with
+struct BepInExSetup {
+    label: String,
+    dir_name: String,
+    dir: PathBuf,
+    /// Games with this backend run with the install
+    backend: Backend,
+    release: BepInExRelease,
+}
+
+/// Backends of the tests, each needs its own BepInEx flavour
+fn test_backends(tests: &[Test]) -> Vec<Backend> {
+    let mut backends = tests.iter().map(Test::backend).collect::<Vec<_>>();
+    backends.sort();
+    backends.dedup();
+    backends
+}
+
+fn plan_bepinex_setups(
+    current_dir: &Path,
+    args: &Args,
+    locked: &[LockedRelease],
+    backends: &[Backend],
+) -> Result<Vec<BepInExSetup>> {
+    let mut setups = Vec::new();
+    for &backend in backends {
+        // a copied BepInEx isn't pinned by the lockfile
+        let releases: Vec<BepInExRelease> = if args.locked && args.bepinex_path.is_none() {
+            let releases = locked
+                .iter()
+                .filter(|locked| locked.backend == backend)
+                .cloned()
+                .map(BepInExRelease::Locked)
+                .collect::<Vec<_>>();
+            if releases.is_empty() {
+                bail!(
+                    "lockfile `{}` has no BepInEx for {backend} games, run `test-runner update` to refresh it",
+                    args.lockfile.display()
+                );
+            }
+            releases
+        } else if args.bepinex_version.is_empty() {
+            vec![BepInExRelease::Version(BepInExVersion::Latest)]
+        } else {
+            args.bepinex_version
+                .iter()
+                .map(|version| BepInExRelease::Version(version.clone()))
+                .collect()
+        };
+
+        let base_name = match backend {
+            Backend::Mono => "BepInEx",
+            Backend::Il2Cpp => "BepInEx-IL2CPP",
+        };
+        let single = releases.len() == 1;
+        for release in releases {
+            let version = match &release {
+                BepInExRelease::Version(version) => version.to_string(),
+                BepInExRelease::Locked(locked) => locked.tag.to_owned(),
+            };
+            let dir_name = if single {
+                base_name.to_string()
+            } else {
+                let version = version
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect::<String>();
+                format!("{base_name}-{version}")
+            };
+            let label = match backend {
+                Backend::Mono => version,
+                Backend::Il2Cpp => format!("{version} IL2CPP"),
+            };
+
+            setups.push(BepInExSetup {
+                label,
+                dir: current_dir.join(&dir_name),
+                dir_name,
+                backend,
+                release,
+            });
+        }
+    }
+
+    Ok(setups)
+}
+
+/// Copies the downloaded games into a fresh directory
+async fn prepare_games_dir(current_dir: &Path, games_dir: &Path, tests: &[&Test]) -> Result<()> {
+    for test in tests {
+        let src = current_dir.join(test.name());
+        let dst = games_dir.join(test.name());
+
+        if dst.is_dir() {
+            fs::remove_dir_all(&dst)
+                .await
+                .with_context(|| format!("failed to remove old game folder `{}`", dst.display()))?;
+        }
+
+        copy_dir_all(&src, &dst).await.with_context(|| {
+            format!(
+                "failed to copy game folder from `{}` to `{}`",
+                src.display(),
+                dst.display()
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+async fn setup_unitas(unitas_dir: &Path, bepinex_dir: &Path) -> Result<()> {
+    copy_dir_all(unitas_dir, bepinex_dir)
+        .await
+        .with_context(|| {
+            format!(
+                "failed to copy UniTAS dir contents from `{}` to game folder `{}`",
+                unitas_dir.display(),
+                bepinex_dir.display()
+            )
+        })
 }
@@ -2,6 +2,21 @@
 
 pub const OLD_INPUT_SYSTEM__2022_3__6000_0_44F1: &str =
     include_str!("movies/old_input_system__2022_3__6000_0_44f1.lua");
+pub const BENCH_FRAME_ADVANCE: &str = include_str!("movies/bench_frame_advance.lua");
+
+/// Movie games can list as a benchmark in the registry
+pub struct Benchmark {
+    pub name: &'static str,
+    pub movie: &'static str,
+    /// Frames the movie plays, the game stops recording after these
+    pub frames: u32,
+}
+
+pub const BENCHMARKS: &[Benchmark] = &[Benchmark {
+    name: "bench_frame_advance",
+    movie: BENCH_FRAME_ADVANCE,
+    frames: 3000,
+}];
 
 #[macro_export]
 macro_rules! movie_name_from_const {
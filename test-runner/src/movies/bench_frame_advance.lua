@@ -0,0 +1,9 @@
+MOVIE_CONFIG = {
+  fps = 60
+}
+
+-- only frame advancing, so frame times are the cost of UniTAS driving each frame
+-- the frame count is also in the benchmark table of movies.rs
+for _ = 1, 3000 do
+  movie.frame_advance()
+end
@@ -0,0 +1,117 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio::fs;
+
+use crate::game_logs::LogEvent;
+
+/// Results of a whole test run, written for CI and other tools to read
+#[derive(Serialize, Default)]
+pub struct Report {
+    pub games: Vec<GameReport>,
+}
+
+#[derive(Serialize)]
+pub struct GameReport {
+    pub game: String,
+    /// BepInEx version the game was tested with
+    pub bepinex: String,
+    /// How the game was started, unset if it never was
+    pub launch: Option<LaunchReport>,
+    /// Effective UniTAS.cfg the game ran with
+    pub unitas_cfg: Option<String>,
+    /// Effective BepInEx.cfg the game ran with
+    pub bepinex_cfg: Option<String>,
+    /// Where logs of the game were collected to
+    pub logs_dir: Option<PathBuf>,
+    /// Errors, fatal entries and exceptions found in the logs which the game doesn't allow
+    pub log_errors: Vec<LogEvent>,
+    /// Time each phase of testing the game took, in order
+    pub phases: Vec<PhaseTiming>,
+    /// Timing of each test result, in order
+    pub timings: Vec<ResultTiming>,
+    pub passed: usize,
+    pub failed: Vec<FailedTest>,
+    /// Set if the game couldn't finish testing, like a crash or connection failure
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LaunchReport {
+    pub args: Vec<String>,
+    /// Variables set on top of the environment of this process
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub wall_secs: f64,
+}
+
+/// How long a test took, all unset for results made by the runner itself
+#[derive(Serialize, Clone, Default)]
+pub struct ResultTiming {
+    pub name: String,
+    /// Real time as the game measured it, which UniTAS may control
+    pub wall_secs: Option<f64>,
+    pub frames: Option<u64>,
+    /// Seconds of `Time.time`
+    pub game_secs: Option<f64>,
+}
+
+impl Display for ResultTiming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(wall_secs) = self.wall_secs {
+            parts.push(format!("{:.0} ms", wall_secs * 1000.0));
+        }
+        if let Some(frames) = self.frames {
+            parts.push(format!("{frames} frames"));
+        }
+        if let Some(game_secs) = self.game_secs {
+            parts.push(format!("{game_secs:.3}s game time"));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[derive(Serialize)]
+pub struct FailedTest {
+    pub name: String,
+    pub message: String,
+}
+
+impl GameReport {
+    pub fn new(game: &str, bepinex: &str) -> Self {
+        Self {
+            game: game.to_string(),
+            bepinex: bepinex.to_string(),
+            launch: None,
+            unitas_cfg: None,
+            bepinex_cfg: None,
+            logs_dir: None,
+            log_errors: Vec::new(),
+            phases: Vec::new(),
+            timings: Vec::new(),
+            passed: 0,
+            failed: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl Report {
+    pub async fn save_json(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self).context("failed to serialize report")?;
+
+        fs::write(path, contents)
+            .await
+            .with_context(|| format!("failed to write report to `{}`", path.display()))
+    }
+}
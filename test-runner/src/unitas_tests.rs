@@ -6,39 +6,138 @@ use std::{
     fs,
     io::{self, Read, Write},
     net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream},
-    path::Path,
-    process::{Command, Stdio},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use crate::{cli::Args, fs_utils::copy_dir_all_blocking, symbols, Os, WIN_UNITY_EXE_NAME};
+use crate::{
+    cfg_files,
+    cli::Args,
+    display::X11Display,
+    fs_utils::{copy_dir_all_blocking, wait_for_stable_size},
+    game_logs::{self, LogEvent, LogFile, LogPolicy},
+    games::{self, Backend, GameSpec},
+    launcher,
+    movies::{self, Benchmark},
+    report::{FailedTest, GameReport, LaunchReport, PhaseTiming, ResultTiming},
+    symbols, Os,
+};
 
 use anyhow::{bail, Context, Result};
 use colored::Colorize;
+use game_output::GameOutput;
 use log::{debug, trace};
+use log_tail::LogTail;
+use regex::Regex;
 use thiserror::Error;
 
+mod benchmark;
+mod game_output;
+mod log_tail;
 mod unity_2022_3_41f1_base;
 mod unity_latest;
 
-pub fn get_linux_tests() -> Vec<Test> {
-    vec![
-        /* unity_2022_3_41f1_base::get(), */ unity_latest::get(),
-    ]
-}
+/// First launch with IL2CPP BepInEx generates interop assemblies before plugins load, which takes a while
+const IL2CPP_MIN_CONNECT_TIMEOUT_SECS: u64 = 300;
+
+type TestFn = fn(ctx: &mut TestCtx, args: &mut TestArgs) -> Result<()>;
+
+/// Test functions games can list as entrypoints in the registry
+const ENTRYPOINTS: &[(&str, TestFn)] = &[
+    ("general", general),
+    (
+        "old_input_system_movie",
+        unity_latest::old_input_system_movie,
+    ),
+    ("unity_2022_3_41f1_base", unity_2022_3_41f1_base::test),
+];
 
-pub fn get_win_tests() -> Vec<Test> {
-    vec![unity_2022_3_41f1_base::get(), unity_latest::get()]
+fn general(ctx: &mut TestCtx, args: &mut TestArgs) -> Result<()> {
+    ctx.run_init_and_general_tests(&mut args.stream)
 }
 
 pub struct Test {
-    name: &'static str,
-    test: fn(ctx: &mut TestCtx, args: TestArgs) -> Result<()>,
+    game: GameSpec,
+    entrypoints: Vec<TestFn>,
+    benchmarks: Vec<&'static Benchmark>,
+    allowed_log_errors: Vec<Regex>,
+}
+
+impl Test {
+    pub fn new(game: GameSpec) -> Result<Self> {
+        let entrypoints = game
+            .entrypoints
+            .iter()
+            .map(|name| {
+                ENTRYPOINTS
+                    .iter()
+                    .find_map(|(entry_name, test)| (entry_name == name).then_some(*test))
+                    .with_context(|| {
+                        let available = ENTRYPOINTS
+                            .iter()
+                            .map(|(name, _)| *name)
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!(
+                            "unknown entrypoint `{name}` for game `{}`, available entrypoints: [{available}]",
+                            game.name
+                        )
+                    })
+            })
+            .collect::<Result<_>>()?;
+        let benchmarks = game
+            .benchmarks
+            .iter()
+            .map(|name| {
+                movies::BENCHMARKS
+                    .iter()
+                    .find(|bench| bench.name == name)
+                    .with_context(|| {
+                        let available = movies::BENCHMARKS
+                            .iter()
+                            .map(|bench| bench.name)
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!(
+                            "unknown benchmark `{name}` for game `{}`, available benchmarks: [{available}]",
+                            game.name
+                        )
+                    })
+            })
+            .collect::<Result<_>>()?;
+        let allowed_log_errors = game
+            .allowed_log_errors
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).with_context(|| {
+                    format!(
+                        "invalid allowed log error pattern `{pattern}` for game `{}`",
+                        game.name
+                    )
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            game,
+            entrypoints,
+            benchmarks,
+            allowed_log_errors,
+        })
+    }
 }
 
 struct TestCtx {
     results: Vec<TestResult>,
+    /// Timing of the results from the game
+    timings: Vec<ResultTiming>,
+    phases: Vec<PhaseTiming>,
+    /// Seconds
+    general_tests_timeout: u64,
+    /// Seconds
+    movie_timeout: u64,
 }
 
 impl TestCtx {
@@ -83,30 +182,40 @@ impl TestCtx {
         );
     }
 
+    /// Runs `f` as a phase, recording how long it took
+    fn phase<T>(&mut self, name: &str, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let start = Instant::now();
+        let result = f(self);
+        self.phases.push(phase_timing(name, start));
+        result
+    }
+
     fn run_init_and_general_tests(&mut self, stream: &mut UniTasStream) -> Result<()> {
-        self.print_test_results(stream, TestType::Init)?;
-        self.run_general_tests_iter(stream)?;
+        self.phase("init", |ctx| ctx.print_test_results(stream, TestType::Init))?;
+        self.phase("general", |ctx| ctx.run_general_tests_iter(stream))?;
 
-        stream.send(
-            "service('IGameRestart').SoftRestart(traverse('DateTime').property('Now').GetValue())",
-        )?;
+        self.phase("soft restart", |_| {
+            stream.send(
+                "service('IGameRestart').SoftRestart(traverse('DateTime').property('Now').GetValue())",
+            )?;
 
-        let mut setup_fail = true;
-        for _ in 0..30 {
-            stream.send("print(service('IGameRestart').Restarting)")?;
-            if stream.receive()? == "false" {
-                setup_fail = false;
-                break;
+            for _ in 0..30 {
+                stream.send("print(service('IGameRestart').Restarting)")?;
+                if stream.receive()? == "false" {
+                    return Ok(());
+                }
+                thread::sleep(Duration::from_secs(1));
             }
-            thread::sleep(Duration::from_secs(1));
-        }
 
-        if setup_fail {
-            panic!("failed to soft restart");
-        }
+            bail!("failed to soft restart");
+        })?;
 
-        self.print_test_results(stream, TestType::Init)?;
-        self.run_general_tests_iter(stream)?;
+        self.phase("init after restart", |ctx| {
+            ctx.print_test_results(stream, TestType::Init)
+        })?;
+        self.phase("general after restart", |ctx| {
+            ctx.run_general_tests_iter(stream)
+        })?;
 
         thread::sleep(Duration::from_secs(1));
 
@@ -118,7 +227,7 @@ impl TestCtx {
         stream.send("traverse('TestFrameworkRuntime').method('RunGeneralTests').GetValue()")?;
 
         let mut timeout = true;
-        for _ in 0..60 {
+        for _ in 0..self.general_tests_timeout {
             stream.send(
                 "print(traverse('TestFrameworkRuntime').field('_generalTestsDone').GetValue())",
             )?;
@@ -130,7 +239,10 @@ impl TestCtx {
         }
 
         if timeout {
-            panic!("failed to finish running general tests");
+            bail!(
+                "failed to finish running general tests after {} seconds",
+                self.general_tests_timeout
+            );
         }
 
         self.print_test_results(stream, TestType::General)?;
@@ -154,9 +266,12 @@ impl TestCtx {
             .parse::<usize>()
             .expect("count of test results should be a number");
 
+        // timing fields are missing in games built before they were added
         stream.send(&format!(
             "local results = traverse('TestFrameworkRuntime').field('_instance').field('{res_field_name}').GetValue() \
-            for _, res in ipairs(results) do print(res.Name) print(res.Success) print(res.Message) end",
+            local function field(res, name) local ok, value = pcall(function() return res[name] end) if ok and value ~= nil then return value end return 'nil' end \
+            for _, res in ipairs(results) do print(res.Name) print(res.Success) print(res.Message) \
+            print(field(res, 'Frames')) print(field(res, 'GameTime')) print(field(res, 'RealTime')) end",
         )
         )?;
 
@@ -164,12 +279,26 @@ impl TestCtx {
             let name = stream.receive()?;
             let success = stream.receive()? == "true";
             let message = stream.receive()?;
+            let mut number = || -> Result<Option<f64>> { Ok(stream.receive()?.parse().ok()) };
+            let timing = ResultTiming {
+                name: name.clone(),
+                frames: number()?.map(|frames| frames as u64),
+                game_secs: number()?,
+                wall_secs: number()?,
+            };
 
+            let timing_text = timing.to_string();
+            let timing_text = if timing_text.is_empty() {
+                String::new()
+            } else {
+                format!(" ({timing_text})").dimmed().to_string()
+            };
             if success {
-                println!("{} {name}", symbols::SUCCESS.green());
+                println!("{} {name}{timing_text}", symbols::SUCCESS.green());
             } else {
-                println!("{} {name}", symbols::FAIL.red());
+                println!("{} {name}{timing_text}", symbols::FAIL.red());
             }
+            self.timings.push(timing);
             let result = if success {
                 TestResult::Success
             } else {
@@ -212,23 +341,20 @@ impl TestCtx {
         ))?;
 
         // wait till movie ends
-        let mut fail = true;
-        for _ in 0..60 {
-            thread::sleep(Duration::from_secs(1));
-            stream.send("print(movie_status().basically_running)")?;
-            if stream.receive()? == "false" {
-                fail = false;
-                break;
+        self.phase(&format!("movie {name}"), |ctx| {
+            for _ in 0..ctx.movie_timeout {
+                thread::sleep(Duration::from_secs(1));
+                stream.send("print(movie_status().basically_running)")?;
+                if stream.receive()? == "false" {
+                    return ctx.print_test_results(stream, TestType::Movie);
+                }
             }
-        }
 
-        if fail {
-            panic!("movie failed to stop running");
-        }
-
-        self.print_test_results(stream, TestType::Movie)?;
-
-        Ok(())
+            bail!(
+                "movie `{name}` failed to stop running after {} seconds",
+                ctx.movie_timeout
+            );
+        })
     }
 }
 
@@ -448,6 +574,33 @@ impl UniTasStream {
 }
 
 impl Test {
+    pub fn name(&self) -> &str {
+        &self.game.name
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.game.backend
+    }
+
+    /// Uses the backend of the downloaded build in `exe_dir` if it differs from the registry
+    pub fn detect_backend(&mut self, exe_dir: &Path) {
+        let game = &mut self.game;
+        let Some(backend) = games::detect_backend(&exe_dir.join(&game.name), &game.exe_name) else {
+            return;
+        };
+
+        if backend != game.backend {
+            println!(
+                "{} game `{}` is declared as {}, but the build is {backend}",
+                symbols::WARN.yellow(),
+                game.name,
+                game.backend
+            );
+            game.backend = backend;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn run(
         &self,
         exe_dir: &Path,
@@ -455,109 +608,71 @@ impl Test {
         logs_dir: &Path,
         os: &Os,
         args: &Args,
+        display: Option<&X11Display>,
+        report: &mut GameReport,
     ) -> Result<(), BatchTestError> {
-        println!("test initialising for {}", self.name);
-
-        let game_dir = exe_dir.join(self.name);
-
-        if !game_dir.is_dir() {
-            panic!("game dir for test unity game doesn't exist");
-        }
-
-        let execute_bin = match os {
-            Os::Linux => "run_bepinex.sh",
-            Os::Windows => WIN_UNITY_EXE_NAME,
-        };
-        let execute_bin = game_dir.join(execute_bin);
-
-        // copy bepinex before running of course
-        copy_dir_all_blocking(bepinex_dir, &game_dir).with_context(|| {
-            format!(
-                "failed to copy BepInEx dir from `{}` to game folder `{}`",
-                bepinex_dir.display(),
-                game_dir.display()
-            )
-        })?;
-
-        // execute game
-        println!("executing unity game");
-        let mut process = Command::new(&execute_bin)
-            .current_dir(&game_dir)
-            .arg("-batchmode")
-            .arg("-nographics")
-            .args(["-logFile", STDOUT_LOG_FILENAME])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .stdin(Stdio::null())
-            .spawn()
-            .with_context(|| {
-                format!(
-                    "failed to run unity game, attempted to run `{}`",
-                    execute_bin.display()
-                )
-            })?;
-
-        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), args.port);
-
-        // now connect
-        let mut stream = None;
-        let fail_secs = 30usize;
-        println!("connecting to UniTAS remote...");
-        for i in 0..fail_secs {
-            match TcpStream::connect_timeout(&addr, Duration::from_secs(30)) {
-                Ok(s) => {
-                    stream = Some(s);
-                    break;
-                }
-                Err(err) => {
-                    // last error?
-                    if i == fail_secs - 1 {
-                        process.kill().context("failed to stop running game")?;
-                        self.move_log(&game_dir, logs_dir);
-
-                        return Err(anyhow::Error::new(err)
-                            .context(format!(
-                                "failed to connect to UniTAS after {fail_secs} seconds"
-                            ))
-                            .into());
-                    }
-
-                    // wait and try again
-                    thread::sleep(Duration::from_secs(1));
-                }
-            }
-        }
-
-        let mut stream = UniTasStream::new(stream.unwrap()).context("failed to initialise connection to UniTAS, verifying connection as a script has failed")?;
-
-        println!("connected\n");
+        let start = Instant::now();
+        let (game, stream) =
+            self.start(exe_dir, bepinex_dir, logs_dir, os, args, display, report)?;
 
-        // get full access of lua api, before moving into test_args
-        stream.send("full_access(true)")?;
-        stream.receive()?;
-
-        let test_args = TestArgs {
-            game_dir: &game_dir,
+        let mut test_args = TestArgs {
+            game_dir: &game.game_dir,
             stream,
         };
         let mut test_ctx = TestCtx {
             results: Vec::new(),
+            timings: Vec::new(),
+            phases: Vec::new(),
+            general_tests_timeout: args.general_tests_timeout,
+            movie_timeout: args.movie_timeout,
         };
 
-        println!("[{}]", self.name);
+        println!("[{}]", self.game.name);
 
         // run tests
-        let result = (self.test)(&mut test_ctx, test_args);
+        let result = self
+            .entrypoints
+            .iter()
+            .try_for_each(|test| test(&mut test_ctx, &mut test_args));
 
         println!();
-        process.kill().context("failed to stop running game")?;
+        report.phases.append(&mut test_ctx.phases);
+        report.timings = std::mem::take(&mut test_ctx.timings);
 
-        let status = process.wait().unwrap();
-        self.move_log(&game_dir, logs_dir);
+        let status = self.stop(game, logs_dir, report, result.is_err())?;
 
         result?;
         println!("test completed\n\n");
 
+        // ignored errors are only in the report
+        if !report.log_errors.is_empty() && !matches!(args.log_errors, LogPolicy::Ignore) {
+            println!(
+                "{} found {} unexpected errors in game logs",
+                symbols::WARN.yellow(),
+                report.log_errors.len()
+            );
+        }
+        match args.log_errors {
+            LogPolicy::Fail => {
+                for event in &report.log_errors {
+                    println!("{} log {}", symbols::FAIL.red(), event.source_name());
+                    test_ctx.results.push(TestResult::Fail(TestFailInfo {
+                        name: format!("log {}", event.source_name()),
+                        message: format!("unexpected error in game logs: {event}"),
+                    }));
+                }
+            }
+            LogPolicy::Warn => {
+                for event in &report.log_errors {
+                    println!(
+                        "{} unexpected error in game logs: {event}",
+                        symbols::WARN.yellow()
+                    );
+                }
+            }
+            LogPolicy::Ignore => {}
+        }
+
         let success_count = test_ctx
             .results
             .iter()
@@ -568,6 +683,15 @@ impl Test {
             TestResult::Fail(info) => Some(info),
         });
 
+        report.passed = success_count;
+        report.failed = fails
+            .clone()
+            .map(|fail| FailedTest {
+                name: fail.name.to_owned(),
+                message: fail.message.to_owned(),
+            })
+            .collect();
+
         let mut fail_count = 0usize;
         for fail in fails.clone() {
             println!("failed test `{}`", fail.name);
@@ -583,12 +707,17 @@ impl Test {
             }
         }
 
+        print_timings(report);
+
         let success = if fail_count == 0 {
             "SUCCESS".green()
         } else {
             "FAILED".red()
         };
-        println!("\ntest result: {success}. {success_count} passed; {fail_count} failed\n\n");
+        println!(
+            "\ntest result: {success}. {success_count} passed; {fail_count} failed; finished in {:.2}s\n\n",
+            start.elapsed().as_secs_f64()
+        );
 
         if fail_count > 0 {
             let signal: Option<i32>;
@@ -618,37 +747,342 @@ impl Test {
         }
     }
 
-    fn move_log(&self, game_dir: &Path, logs_dir: &Path) {
-        let log_src = game_dir.join(STDOUT_LOG_FILENAME);
-        let log_dst = logs_dir.join(format!("{}-{STDOUT_LOG_FILENAME}", self.name));
-        if let Err(err) = fs::copy(&log_src, &log_dst) {
-            eprintln!(
-                "{} failed to copy stdout log file from `{}` to `{}`: {err}",
-                symbols::WARN.yellow(),
-                log_src.display(),
-                log_dst.display()
-            );
+    /// Sets up BepInEx and the config files in the game dir, launches the game and connects to UniTAS
+    #[allow(clippy::too_many_arguments)]
+    fn start(
+        &self,
+        exe_dir: &Path,
+        bepinex_dir: &Path,
+        logs_dir: &Path,
+        os: &Os,
+        args: &Args,
+        display: Option<&X11Display>,
+        report: &mut GameReport,
+    ) -> Result<(GameProcess, UniTasStream), BatchTestError> {
+        let game = &self.game;
+        match &game.unity_version {
+            Some(version) => println!(
+                "test initialising for {} (Unity {version}, {})",
+                game.name, game.backend
+            ),
+            None => println!("test initialising for {}", game.name),
+        }
+
+        let game_dir = exe_dir.join(&game.name);
+
+        if !game_dir.is_dir() {
+            panic!("game dir for test unity game doesn't exist");
+        }
+        let start = Instant::now();
+
+        // copy bepinex before running of course
+        copy_dir_all_blocking(bepinex_dir, &game_dir).with_context(|| {
+            format!(
+                "failed to copy BepInEx dir from `{}` to game folder `{}`",
+                bepinex_dir.display(),
+                game_dir.display()
+            )
+        })?;
+        report.unitas_cfg = Some(cfg_files::write_unitas_cfg(&game_dir, game, args)?);
+        report.bepinex_cfg = Some(cfg_files::write_bepinex_cfg(&game_dir, game, args)?);
+        let launch = launcher::prepare(&game_dir, &game.exe_file(os), os, args.launch_mode)?;
+        let execute_bin = launch.program;
+
+        // execute game
+        println!("executing unity game");
+        let mut launch_args = Vec::new();
+        let mut env = launch.env;
+        match display {
+            None => launch_args.extend(["-batchmode".to_string(), "-nographics".to_string()]),
+            // mesa software rendering, so it runs without a gpu
+            Some(display) => {
+                env.insert("DISPLAY".to_string(), display.name().to_string());
+                env.insert("LIBGL_ALWAYS_SOFTWARE".to_string(), "1".to_string());
+                launch_args.extend([
+                    "-force-glcore".to_string(),
+                    "-screen-fullscreen".to_string(),
+                    "0".to_string(),
+                    "-screen-width".to_string(),
+                    args.resolution.width.to_string(),
+                    "-screen-height".to_string(),
+                    args.resolution.height.to_string(),
+                ]);
+            }
+        };
+        launch_args.extend(["-logFile".to_string(), STDOUT_LOG_FILENAME.to_string()]);
+        // game specific, then the ones for the whole run
+        launch_args.extend(game.args.iter().cloned());
+        launch_args.extend(args.game_arg.iter().cloned());
+        launcher::extend_env(&mut env, game.env.clone());
+        launcher::extend_env(&mut env, args.game_env.iter().cloned());
+
+        report.launch = Some(LaunchReport {
+            args: launch_args.clone(),
+            env: env.clone(),
+        });
+
+        // logs of an earlier run would otherwise be followed and collected
+        let game_logs = [
+            (
+                LogFile::UniTas,
+                game_dir.join("BepInEx").join(UNITAS_LOG_FILENAME),
+            ),
+            (LogFile::Player, game_dir.join(STDOUT_LOG_FILENAME)),
+        ];
+        for (_, path) in &game_logs {
+            if let Err(err) = fs::remove_file(path) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    return Err(anyhow::Error::new(err)
+                        .context(format!("failed to remove old log `{}`", path.display()))
+                        .into());
+                }
+            }
+        }
+
+        report.phases.push(phase_timing("setup", start));
+        let connect_start = Instant::now();
+
+        let mut command = Command::new(&execute_bin);
+        let mut process = command
+            .current_dir(&game_dir)
+            .args(&launch_args)
+            .envs(&env)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .stdin(Stdio::null())
+            .spawn()
+            .with_context(|| {
+                format!(
+                    "failed to run unity game, attempted to run `{}`",
+                    execute_bin.display()
+                )
+            })?;
+
+        let output =
+            match game_output::capture(&mut process, logs_dir, &game.name, args.tee_game_output) {
+                Ok(output) => output,
+                Err(err) => {
+                    process.kill().context("failed to stop running game")?;
+                    return Err(err.into());
+                }
+            };
+
+        let tail = LogTail::start(
+            game_logs.to_vec(),
+            &game.name,
+            args.tail_logs,
+            args.tail_lines,
+        );
+        let process = GameProcess {
+            game_dir,
+            process,
+            output,
+            tail,
+        };
+
+        let fail_secs = match game.backend {
+            Backend::Mono => args.remote_connect_timeout.max(1),
+            Backend::Il2Cpp => args
+                .remote_connect_timeout
+                .max(IL2CPP_MIN_CONNECT_TIMEOUT_SECS),
+        };
+        let stream = connect(args.port, fail_secs);
+        report.phases.push(phase_timing("connect", connect_start));
+        match stream {
+            Ok(stream) => Ok((process, stream)),
+            Err(err) => {
+                self.stop(process, logs_dir, report, true)?;
+                Err(err.into())
+            }
         }
+    }
 
-        // 2 seconds to flush usually
-        thread::sleep(Duration::from_millis(2500));
+    /// Stops the game and collects its logs, printing their last lines if the game `failed`
+    fn stop(
+        &self,
+        game: GameProcess,
+        logs_dir: &Path,
+        report: &mut GameReport,
+        failed: bool,
+    ) -> Result<ExitStatus> {
+        let GameProcess {
+            game_dir,
+            mut process,
+            output,
+            mut tail,
+        } = game;
+        let shutdown_start = Instant::now();
 
-        let log_src = game_dir.join("BepInEx").join("UniTAS.log");
-        let log_dst = logs_dir.join(format!("{}.log", self.name));
-        if let Err(err) = fs::copy(&log_src, &log_dst) {
+        process.kill().context("failed to stop running game")?;
+        let status = process
+            .wait()
+            .context("failed to wait for the game to exit")?;
+        if failed {
+            tail.print_last_lines();
+        } else {
+            tail.stop();
+        }
+        Self::move_log(&game_dir, logs_dir, &output);
+        report.log_errors = self.unexpected_log_errors(logs_dir);
+        report.phases.push(phase_timing("shutdown", shutdown_start));
+
+        Ok(status)
+    }
+
+    /// Errors in the logs collected into `logs_dir` which the game doesn't allow
+    fn unexpected_log_errors(&self, logs_dir: &Path) -> Vec<LogEvent> {
+        Self::log_events(logs_dir)
+            .into_iter()
+            .filter(|event| event.is_error() && !event.matches_any(&self.allowed_log_errors))
+            .collect()
+    }
+
+    /// Events of the logs collected into `logs_dir`
+    fn log_events(logs_dir: &Path) -> Vec<LogEvent> {
+        let mut events = Vec::new();
+        for file in [LogFile::UniTas, LogFile::Player] {
+            let path = logs_dir.join(file.to_string());
+            match game_logs::parse_file(&path, file) {
+                Ok(file_events) => events.extend(file_events),
+                Err(err) => eprintln!("{} {err:#}", symbols::WARN.yellow()),
+            }
+        }
+
+        events
+    }
+
+    /// Copies logs of the exited game once they, and the game output, stop being written to
+    fn move_log(game_dir: &Path, logs_dir: &Path, output: &GameOutput) {
+        let logs = vec![
+            (
+                game_dir.join(STDOUT_LOG_FILENAME),
+                logs_dir.join(STDOUT_LOG_FILENAME),
+            ),
+            (
+                game_dir.join("BepInEx").join(UNITAS_LOG_FILENAME),
+                logs_dir.join(UNITAS_LOG_FILENAME),
+            ),
+        ];
+        // children of the game can still be flushing after it exits, which includes the game
+        // output already written into `logs_dir`
+        let sources = logs
+            .iter()
+            .map(|(src, _)| src.as_path())
+            .chain(output.files.iter().map(PathBuf::as_path))
+            .collect::<Vec<_>>();
+        if !wait_for_stable_size(&sources, LOG_FLUSH_TIMEOUT) {
             eprintln!(
-                "{} failed to copy log file from `{}` to `{}`: {err}",
+                "{} logs were still being written after {} seconds, copying them anyway",
                 symbols::WARN.yellow(),
-                log_src.display(),
-                log_dst.display()
+                LOG_FLUSH_TIMEOUT.as_secs()
             );
         }
 
+        for (log_src, log_dst) in logs {
+            if let Err(err) = fs::copy(&log_src, &log_dst) {
+                eprintln!(
+                    "{} failed to copy log file from `{}` to `{}`: {err}",
+                    symbols::WARN.yellow(),
+                    log_src.display(),
+                    log_dst.display()
+                );
+            }
+        }
+
         println!("moved log of last session into `{}`", logs_dir.display());
     }
 }
 
+/// Game started by [`Test::start`], stopped with [`Test::stop`]
+struct GameProcess {
+    game_dir: PathBuf,
+    process: Child,
+    output: GameOutput,
+    tail: LogTail,
+}
+
+/// Connects to the UniTAS remote as a script with full access to the lua api
+fn connect(port: u16, fail_secs: u64) -> Result<UniTasStream> {
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+
+    println!("connecting to UniTAS remote...");
+    let mut stream = None;
+    for i in 0..fail_secs {
+        match TcpStream::connect_timeout(&addr, Duration::from_secs(30)) {
+            Ok(s) => {
+                stream = Some(s);
+                break;
+            }
+            Err(err) => {
+                // last error?
+                if i == fail_secs - 1 {
+                    return Err(anyhow::Error::new(err).context(format!(
+                        "failed to connect to UniTAS after {fail_secs} seconds"
+                    )));
+                }
+
+                // wait and try again
+                thread::sleep(Duration::from_secs(1));
+            }
+        }
+    }
+
+    let mut stream = UniTasStream::new(stream.unwrap()).context(
+        "failed to initialise connection to UniTAS, verifying connection as a script has failed",
+    )?;
+
+    println!("connected\n");
+
+    stream.send("full_access(true)")?;
+    stream.receive()?;
+
+    Ok(stream)
+}
+
+/// Tests with the longest wall time printed in the summary
+const SLOWEST_TESTS_SHOWN: usize = 5;
+
+fn phase_timing(name: &str, start: Instant) -> PhaseTiming {
+    PhaseTiming {
+        name: name.to_string(),
+        wall_secs: start.elapsed().as_secs_f64(),
+    }
+}
+
+fn print_timings(report: &GameReport) {
+    println!("\nphases:");
+    let name_width = report
+        .phases
+        .iter()
+        .map(|phase| phase.name.len())
+        .max()
+        .unwrap_or_default();
+    for phase in &report.phases {
+        println!("    {:name_width$}  {:>8.2}s", phase.name, phase.wall_secs);
+    }
+
+    let mut slowest = report
+        .timings
+        .iter()
+        .filter(|timing| timing.wall_secs.is_some())
+        .collect::<Vec<_>>();
+    if slowest.is_empty() {
+        return;
+    }
+    slowest.sort_by(|a, b| {
+        let secs = |timing: &ResultTiming| timing.wall_secs.unwrap_or_default();
+        secs(b).total_cmp(&secs(a))
+    });
+    println!("\nslowest tests:");
+    for timing in slowest.into_iter().take(SLOWEST_TESTS_SHOWN) {
+        println!("    {} ({timing})", timing.name);
+    }
+}
+
 const STDOUT_LOG_FILENAME: &str = "stdout.log";
+const UNITAS_LOG_FILENAME: &str = "UniTAS.log";
+/// Longest wait for logs to be flushed after the game exits
+const LOG_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Error, Debug)]
 pub enum BatchTestError {
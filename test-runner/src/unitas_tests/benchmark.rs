@@ -0,0 +1,152 @@
+//! Plays benchmark movies, timing the frames the test framework in the game counts
+
+use super::*;
+use crate::bench::BenchRun;
+
+/// Runs of each benchmark, by benchmark name
+pub type BenchSamples = Vec<(String, Vec<BenchRun>)>;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+impl Test {
+    pub fn has_benchmarks(&self) -> bool {
+        !self.benchmarks.is_empty()
+    }
+
+    /// Plays every benchmark movie of the game `runs` times in one game session
+    #[allow(clippy::too_many_arguments)]
+    pub fn bench(
+        &self,
+        exe_dir: &Path,
+        bepinex_dir: &Path,
+        logs_dir: &Path,
+        os: &Os,
+        args: &Args,
+        display: Option<&X11Display>,
+        runs: u32,
+        report: &mut GameReport,
+    ) -> Result<BenchSamples, BatchTestError> {
+        let (game, mut stream) =
+            self.start(exe_dir, bepinex_dir, logs_dir, os, args, display, report)?;
+
+        println!("[{}]", self.game.name);
+        let mut samples = Vec::new();
+        let result = self.benchmarks.iter().try_for_each(|bench| {
+            let name = bench.name;
+            let mut bench_runs = Vec::new();
+            for run in 1..=runs {
+                let start = Instant::now();
+                let bench_run =
+                    play_benchmark(&mut stream, bench, &game.game_dir, args.movie_timeout)?;
+                report
+                    .phases
+                    .push(phase_timing(&format!("bench {name} #{run}"), start));
+                println!(
+                    "{} {name} run {run}/{runs}: {} frames in {:.3} seconds",
+                    symbols::SUCCESS.green(),
+                    bench_run.frames,
+                    bench_run.secs
+                );
+                bench_runs.push(bench_run);
+            }
+            samples.push((name.to_owned(), bench_runs));
+            anyhow::Ok(())
+        });
+        println!();
+
+        self.stop(game, logs_dir, report, result.is_err())?;
+        result?;
+
+        Ok(samples)
+    }
+}
+
+/// Plays the movie, timing the frames the game records from after the restart it does until the movie ends
+///
+/// The game can't time them itself, UniTAS controls its clocks while the movie plays
+fn play_benchmark(
+    stream: &mut UniTasStream,
+    bench: &Benchmark,
+    game_dir: &Path,
+    timeout: u64,
+) -> Result<BenchRun> {
+    let name = bench.name;
+    let dest = game_dir.join(format!("{name}.lua"));
+    fs::write(&dest, bench.movie)
+        .with_context(|| format!("failed to write movie file to `{}`", dest.display()))?;
+
+    // the restart resets static fields, so recording starts after it, stopping by itself once the
+    // frames of the movie are recorded
+    stream.send(&format!(
+        r#"
+        local function on_restart(_, pre_scene_load)
+            if pre_scene_load then
+                return
+            end
+            hook_on_game_restart(on_restart, false)
+
+            traverse("TestFrameworkRuntime").field("_benchmarkFrames").SetValue({})
+            traverse("TestFrameworkRuntime").method("StartBenchmark").GetValue()
+        end
+
+        hook_on_game_restart(on_restart, true)
+        play("{}")
+        "#,
+        bench.frames,
+        dest.to_string_lossy()
+    ))?;
+
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    let timed_out = || bail!("benchmark `{name}` failed to stop running after {timeout} seconds");
+
+    // timed from the first frame seen, as the restart before it isn't part of the benchmark
+    let (start, start_frames) = loop {
+        let frames = recorded_frames(stream)?;
+        if frames > 0 {
+            break (Instant::now(), frames);
+        }
+        if Instant::now() > deadline {
+            return timed_out();
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    let end = loop {
+        if recorded_frames(stream)? >= bench.frames {
+            break Instant::now();
+        }
+        if Instant::now() > deadline {
+            return timed_out();
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    if start_frames >= bench.frames {
+        bail!("benchmark `{name}` ended before its frames could be timed, it needs more frames");
+    }
+
+    // the next run can't start while the last frames of this one are still playing
+    loop {
+        stream.send("print(movie_status().basically_running)")?;
+        if stream.receive()? == "false" {
+            break;
+        }
+        if Instant::now() > deadline {
+            return timed_out();
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    Ok(BenchRun {
+        frames: bench.frames - start_frames,
+        secs: (end - start).as_secs_f64(),
+    })
+}
+
+/// Frames the game recorded since the benchmark started
+fn recorded_frames(stream: &mut UniTasStream) -> Result<u32> {
+    stream
+        .send("print(traverse('TestFrameworkRuntime').method('GetBenchmarkFrames').GetValue())")?;
+    let frames = stream.receive()?;
+    frames.parse::<f64>().map(|frames| frames as u32).with_context(|| {
+        format!("invalid recorded frame count `{frames}` from the game, it may be built without benchmark support")
+    })
+}
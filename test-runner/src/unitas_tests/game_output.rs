@@ -0,0 +1,120 @@
+//! Captures stdout and stderr of the game process, which has output from `run_bepinex.sh`, Doorstop and native crashes
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::Child,
+    thread,
+};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use log::debug;
+
+use crate::logs::TIMESTAMP_FORMAT;
+
+/// Files the game output is written to
+pub struct GameOutput {
+    pub files: Vec<PathBuf>,
+}
+
+/// Writes both streams of the process into `game-stdout-<timestamp>.log` and `game-stderr-<timestamp>.log` in `dir`, the logs dir of the run
+///
+/// With `tee`, lines are also printed prefixed with the game name
+pub fn capture(process: &mut Child, dir: &Path, game_name: &str, tee: bool) -> Result<GameOutput> {
+    let timestamp = chrono::Local::now().format(TIMESTAMP_FORMAT);
+    let prefix = tee.then(|| format!("[{game_name}]").dimmed().to_string());
+    let mut files = Vec::new();
+
+    if let Some(stdout) = process.stdout.take() {
+        let path = dir.join(format!("game-stdout-{timestamp}.log"));
+        spawn_forward(stdout, &path, prefix.clone(), false)?;
+        files.push(path);
+    }
+    if let Some(stderr) = process.stderr.take() {
+        let path = dir.join(format!("game-stderr-{timestamp}.log"));
+        spawn_forward(stderr, &path, prefix, true)?;
+        files.push(path);
+    }
+
+    Ok(GameOutput { files })
+}
+
+fn spawn_forward(
+    stream: impl Read + Send + 'static,
+    path: &Path,
+    prefix: Option<String>,
+    is_stderr: bool,
+) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("failed to create game output file `{}`", path.display()))?;
+
+    // not joined, the pipes stay open for as long as any child of the game does
+    thread::spawn(move || forward(stream, file, prefix, is_stderr));
+    Ok(())
+}
+
+fn forward(stream: impl Read, mut file: File, prefix: Option<String>, is_stderr: bool) {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                debug!("failed to read game output: {err}");
+                break;
+            }
+        }
+
+        // flushed per line, so the file is complete once the game is gone
+        if let Err(err) = file.write_all(&line).and_then(|_| file.flush()) {
+            debug!("failed to write game output: {err}");
+            break;
+        }
+
+        if let Some(prefix) = &prefix {
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end();
+            if is_stderr {
+                eprintln!("{prefix} {text}");
+            } else {
+                println!("{prefix} {text}");
+            }
+        }
+    }
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use std::{
+        fs,
+        process::{Command, Stdio},
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn both_streams_are_written_to_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut process = Command::new("sh")
+            .args(["-c", "echo out; echo err >&2"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let output = capture(&mut process, dir.path(), "game", false).unwrap();
+        process.wait().unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        let [stdout, stderr] = &output.files[..] else {
+            panic!("expected 2 output files, got {:?}", output.files);
+        };
+        assert_eq!(fs::read_to_string(stdout).unwrap(), "out\n");
+        assert_eq!(fs::read_to_string(stderr).unwrap(), "err\n");
+    }
+}
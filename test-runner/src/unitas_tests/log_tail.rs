@@ -0,0 +1,217 @@
+//! Follows `UniTAS.log` and the Unity `stdout.log` while the game runs
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use colored::{ColoredString, Colorize};
+use log::debug;
+
+use crate::game_logs::{self, Level, LogFile};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+struct TailedFile {
+    file: LogFile,
+    path: PathBuf,
+    offset: u64,
+    /// Last line, until its newline is written
+    partial: Vec<u8>,
+    last_lines: VecDeque<String>,
+}
+
+/// Polls log files for new lines, keeping the last ones of each file and optionally printing them as they come
+pub struct LogTail {
+    files: Arc<Mutex<Vec<TailedFile>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LogTail {
+    /// With `print`, new lines are printed prefixed with the game name and log file
+    pub fn start(
+        logs: Vec<(LogFile, PathBuf)>,
+        game_name: &str,
+        print: bool,
+        keep_lines: usize,
+    ) -> Self {
+        let files = logs
+            .into_iter()
+            .map(|(file, path)| TailedFile {
+                file,
+                path,
+                offset: 0,
+                partial: Vec::new(),
+                last_lines: VecDeque::new(),
+            })
+            .collect::<Vec<_>>();
+        let files = Arc::new(Mutex::new(files));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let files = files.clone();
+            let stop = stop.clone();
+            let game_name = game_name.to_string();
+            thread::spawn(move || {
+                // one more poll after stopping, so lines written right before aren't missed
+                loop {
+                    let stopping = stop.load(Ordering::Relaxed);
+                    for tailed in files.lock().unwrap().iter_mut() {
+                        for line in tailed.poll(keep_lines) {
+                            if print {
+                                println!("{} {}", prefix(&game_name, tailed.file), colour(&line));
+                            }
+                        }
+                    }
+                    if stopping {
+                        break;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+            })
+        };
+
+        Self {
+            files,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Prints the last lines of every log, for when the game timed out or crashed
+    pub fn print_last_lines(&mut self) {
+        self.stop();
+
+        for tailed in self.files.lock().unwrap().iter() {
+            if tailed.last_lines.is_empty() {
+                continue;
+            }
+            println!("last {} lines of {}:", tailed.last_lines.len(), tailed.file);
+            for line in &tailed.last_lines {
+                println!("{} {}", "|".dimmed(), colour(line));
+            }
+            println!();
+        }
+    }
+
+    /// Reads what is left and stops following the logs
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for LogTail {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl TailedFile {
+    /// Complete lines written since the last poll
+    fn poll(&mut self, keep_lines: usize) -> Vec<String> {
+        let mut new = Vec::new();
+        let Ok(mut file) = File::open(&self.path) else {
+            return Vec::new();
+        };
+        let Ok(len) = file.metadata().map(|metadata| metadata.len()) else {
+            return Vec::new();
+        };
+        // rewritten from the start
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if len == self.offset {
+            return Vec::new();
+        }
+
+        if let Err(err) = file
+            .seek(SeekFrom::Start(self.offset))
+            .and_then(|_| file.read_to_end(&mut new))
+        {
+            debug!("failed to read log `{}`: {err}", self.path.display());
+            return Vec::new();
+        }
+        self.offset += new.len() as u64;
+        self.partial.extend(new);
+
+        let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return Vec::new();
+        };
+        let complete = self.partial.drain(..=end).collect::<Vec<_>>();
+        let lines = String::from_utf8_lossy(&complete)
+            .lines()
+            .map(|line| line.trim_end().to_string())
+            .collect::<Vec<_>>();
+
+        for line in &lines {
+            if self.last_lines.len() == keep_lines {
+                self.last_lines.pop_front();
+            }
+            if keep_lines > 0 {
+                self.last_lines.push_back(line.clone());
+            }
+        }
+
+        lines
+    }
+}
+
+fn prefix(game_name: &str, file: LogFile) -> ColoredString {
+    format!("[{game_name} {file}]").dimmed()
+}
+
+fn colour(line: &str) -> ColoredString {
+    match game_logs::line_level(line) {
+        Some(Level::Fatal | Level::Error) => line.red(),
+        Some(Level::Warning) => line.yellow(),
+        Some(Level::Debug) => line.dimmed(),
+        _ => line.normal(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use super::*;
+
+    #[test]
+    fn keeps_last_lines_of_growing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("UniTAS.log");
+        let mut tailed = TailedFile {
+            file: LogFile::UniTas,
+            path: path.clone(),
+            offset: 0,
+            partial: Vec::new(),
+            last_lines: VecDeque::new(),
+        };
+        assert!(tailed.poll(2).is_empty());
+
+        let mut log = File::create(&path).unwrap();
+        log.write_all(b"one\ntwo\nthr").unwrap();
+        assert_eq!(tailed.poll(2), ["one", "two"]);
+
+        log.write_all(b"ee\nfour\n").unwrap();
+        assert_eq!(tailed.poll(2), ["three", "four"]);
+        assert_eq!(tailed.last_lines, ["three", "four"]);
+
+        // written again from the start by a new game
+        fs::write(&path, "new\n").unwrap();
+        assert_eq!(tailed.poll(2), ["new"]);
+        assert_eq!(tailed.last_lines, ["four", "new"]);
+    }
+}
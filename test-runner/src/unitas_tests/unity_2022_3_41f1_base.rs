@@ -1,19 +1,12 @@
 use std::fs;
 
-use super::{Test, TestArgs, TestCtx, TestType};
+use super::{TestArgs, TestCtx, TestType};
 
 use anyhow::{Context, Result};
 
 const MOVIE: &str = include_str!("unity_2022_3_41f1_base_movie.lua");
 
-pub fn get() -> Test {
-    Test {
-        name: "2022.3.41f1-base",
-        test,
-    }
-}
-
-fn test(ctx: &mut TestCtx, mut args: TestArgs) -> Result<()> {
+pub fn test(ctx: &mut TestCtx, args: &mut TestArgs) -> Result<()> {
     let movie_path = args.game_dir.join("movie.lua");
     fs::write(&movie_path, MOVIE).with_context(|| {
         format!(